@@ -2,8 +2,10 @@
 
 import type {
   DownloadArgs,
+  ExecEnvArgs,
   InstallArgs,
   InstallConfigSimple,
+  ListBinPathsArgs,
 } from "../src/deno_ports/mod.ts";
 import {
   $,
@@ -25,6 +27,8 @@ const manifest = {
 
 const confValidator = zod.object({
   output: zod.string().nullish(),
+  // replaces the default env vars of the install
+  env: zod.record(zod.string(), zod.string()).nullish(),
 });
 
 export type DummyInstallConf =
@@ -39,8 +43,9 @@ export default function conf(config: DummyInstallConf = {}) {
 }
 
 export class Port extends PortBase {
-  override execEnv() {
-    return {
+  override execEnv(args: ExecEnvArgs) {
+    const conf = confValidator.parse(args.config);
+    return conf.env ?? {
       DUMMY_ENV: "dummy",
     };
   }
@@ -59,6 +64,26 @@ echo ${conf.output ?? "dummy hey"}`,
         mode: 0o700,
       },
     );
+    // files for the other shim dirs
+    const downloadPath = $.path(args.downloadPath);
+    await downloadPath.join("lib", "pkgconfig", "dummy.pc").writeText(
+      `Name: dummy
+Description: a dumb package
+Version: 0.1.0
+`,
+    );
+    await downloadPath.join("lib", "cmake", "Dummy", "DummyConfig.cmake")
+      .writeText(`set(DUMMY_FOUND TRUE)\n`);
+    await downloadPath.join("share", "man", "man1", "dummy.1").writeText(
+      `.TH DUMMY 1\n.SH NAME\ndummy \\- a dumb program\n`,
+    );
+    await downloadPath.join("share", "dummy", "greeting.txt").writeText(
+      `${conf.output ?? "dummy hey"}\n`,
+    );
+  }
+
+  override listSharePaths(args: ListBinPathsArgs) {
+    return [$.path(args.installPath).join("share").toString()];
   }
 
   override async install(args: InstallArgs) {
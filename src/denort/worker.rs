@@ -95,9 +95,13 @@ async fn module_worker(
             while let Some(msg) = module_rx.recv().await {
                 trace!(?msg, "module worker msg");
                 match msg {
-                    ModuleWorkerReq::Run { response_channel } => response_channel
-                        .send(module_cx.run(global_term_signal.clone()).await)
-                        .expect_or_log("channel error"),
+                    ModuleWorkerReq::Run { response_channel } => {
+                        let res = module_cx.run(global_term_signal.clone()).await;
+                        // the requester might have given up waiting on the run
+                        if response_channel.send(res).is_err() {
+                            trace!("module run result dropped");
+                        }
+                    }
                     ModuleWorkerReq::DriveTillExit {
                         term_signal,
                         response_channel,
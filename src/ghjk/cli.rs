@@ -9,6 +9,7 @@ use crate::systems::SystemManifest;
 use crate::{host, systems};
 
 mod init;
+mod lock;
 mod print;
 mod reducers;
 mod sys;
@@ -104,21 +105,40 @@ pub async fn cli() -> Res<std::process::ExitCode> {
         (manifests, envs_ctx, deno_sys_cx)
     };
 
+    let mut host_args = HostArgs::parse_early();
+    debug!(?host_args, "host args parsed");
+    if let QuickCliResult::LockCheck { .. } = quick_res {
+        // the check compares against a fresh serialization
+        // and never writes so the other flags don't apply
+        host_args = HostArgs {
+            locked: false,
+            re_serialize: true,
+            re_resolve: false,
+        };
+    }
+
     let hcx = host::HostCtx::new(
         gcx.clone(),
         host::Config {
             env_vars: std::env::vars().collect(),
             cwd,
-            // TODO: env vars, flags and tests for the following
-            re_resolve: false,
-            locked: false,
-            re_serialize: false,
+            re_resolve: host_args.re_resolve,
+            locked: host_args.locked,
+            re_serialize: host_args.re_serialize,
         },
         system_manifests,
+        host::DigestMemo::load(&ghjkdir_path).await,
     );
 
     let hcx = Arc::new(hcx);
 
+    if let QuickCliResult::LockCheck { json } = quick_res {
+        let res = lock::check(hcx, &ghjkdir_path, json).await;
+        deno_sys_cx.terminate().await?;
+        deno_cx.terminate().await?;
+        return res;
+    }
+
     // initialize the systems according to the config
     let mut systems = {
         let is_completions = matches!(quick_res, QuickCliResult::Completions(_));
@@ -181,6 +201,7 @@ pub async fn cli() -> Res<std::process::ExitCode> {
                         &root_cmd,
                         &sys_cmds,
                         &sys_actions,
+                        envs_ctx.completion_candidates(),
                         // TODO: optional_aliases
                         true,
                     )
@@ -210,7 +231,7 @@ pub async fn cli() -> Res<std::process::ExitCode> {
 
     match QuickCommands::from_arg_matches(&matches) {
         Ok(QuickCommands::Print { commands }) => {
-            _ = commands.action(&gcx.config, Some(&systems.config))?;
+            _ = commands.action(&gcx.config, Some(&systems.config)).await?;
             return Ok(ExitCode::SUCCESS);
         }
         Ok(QuickCommands::Completions { .. }) => {
@@ -219,6 +240,9 @@ pub async fn cli() -> Res<std::process::ExitCode> {
         Ok(QuickCommands::Init { .. }) => {
             unreachable!("quick_cli will prevent this")
         }
+        Ok(QuickCommands::Lock { .. }) => {
+            unreachable!("quick_cli will prevent this")
+        }
         Ok(QuickCommands::Deno { .. }) => {
             unreachable!("deno_quick_cli will prevent this")
         }
@@ -256,7 +280,15 @@ pub async fn cli() -> Res<std::process::ExitCode> {
         .await
         .wrap_err_with(|| format!("error on system command at path {cmd_path:?}"));
 
-    systems.write_lockfile_or_log().await;
+    // lockfile errors only fail the command if it was otherwise successful
+    // which is what makes `--locked` useful in CI
+    let res = match res {
+        Ok(()) => systems.write_lockfile().await,
+        Err(err) => {
+            systems.write_lockfile_or_log().await;
+            Err(err)
+        }
+    };
 
     deno_sys_cx.terminate().await?;
     deno_cx.terminate().await?;
@@ -267,6 +299,10 @@ pub async fn cli() -> Res<std::process::ExitCode> {
 enum QuickCliResult {
     ClapErr(clap::Error),
     Completions(CompletionShell),
+    /// `lock check` needs the systems but not the ghjkfile's commands.
+    LockCheck {
+        json: bool,
+    },
     Exit(ExitCode),
 }
 impl QuickCliResult {
@@ -292,6 +328,10 @@ impl QuickCliResult {
                 );
                 ExitCode::SUCCESS
             }
+            QuickCliResult::LockCheck { .. } => {
+                error!("no ghjkdir found");
+                ExitCode::FAILURE
+            }
             QuickCliResult::Exit(_) => unreachable!("can't happen"),
         }
     }
@@ -301,6 +341,10 @@ impl QuickCliResult {
 async fn try_quick_cli(config: &Config) -> Res<QuickCliResult> {
     use clap::*;
 
+    if let Some(code) = try_envs_status_quick_cli(config).await? {
+        return Ok(QuickCliResult::Exit(code));
+    }
+
     let cli = match Cli::try_parse() {
         Ok(val) => val,
         Err(err) => {
@@ -319,7 +363,7 @@ async fn try_quick_cli(config: &Config) -> Res<QuickCliResult> {
 
     match cli.quick_commands {
         QuickCommands::Print { commands } => {
-            if !commands.action(config, None)? {
+            if !commands.action(config, None).await? {
                 return Ok(QuickCliResult::ClapErr(clap::error::Error::new(
                     clap::error::ErrorKind::DisplayHelp,
                 )));
@@ -332,12 +376,44 @@ async fn try_quick_cli(config: &Config) -> Res<QuickCliResult> {
             return Ok(QuickCliResult::Completions(shell));
         }
         QuickCommands::Init { commands } => commands.action(config).await?,
+        QuickCommands::Lock {
+            commands: lock::LockCommands::Check { json },
+        } => return Ok(QuickCliResult::LockCheck { json }),
+        QuickCommands::Lock { commands } => commands.action(config).await?,
         QuickCommands::Deno { .. } => unreachable!("deno quick cli will have prevented this"),
     }
 
     Ok(QuickCliResult::Exit(ExitCode::SUCCESS))
 }
 
+/// `ghjk envs status` is handled ahead of the deno runtime to keep
+/// it cheap enough for use in the shell hooks.
+async fn try_envs_status_quick_cli(config: &Config) -> Res<Option<ExitCode>> {
+    use clap::Parser;
+
+    let argv = std::env::args().skip(1).collect::<Vec<_>>();
+    if !matches!(
+        argv.iter().map(|arg| &arg[..]).take(2).collect::<Vec<_>>()[..],
+        ["envs" | "e", "status"]
+    ) {
+        return Ok(None);
+    }
+    let args = match systems::envs::StatusArgs::try_parse_from(&argv[1..]) {
+        Ok(val) => val,
+        Err(err) => err.exit(),
+    };
+    let Some(ghjkdir_path) = &config.ghjkdir else {
+        eyre::bail!("no ghjkdir found");
+    };
+    let status = if args.auto_sync {
+        systems::envs::env_status_auto_sync(config, ghjkdir_path, args.env.as_deref()).await?
+    } else {
+        systems::envs::env_status(config, ghjkdir_path, args.env.as_deref()).await?
+    };
+    println!("{status}");
+    Ok(Some(ExitCode::SUCCESS))
+}
+
 const CLAP_STYLE: clap::builder::Styles = clap::builder::Styles::styled()
     .header(AnsiColor::Yellow.on_default())
     .usage(AnsiColor::BrightBlue.on_default())
@@ -351,10 +427,69 @@ const CLAP_STYLE: clap::builder::Styles = clap::builder::Styles::styled()
     styles = CLAP_STYLE
 )]
 struct Cli {
+    #[command(flatten)]
+    host_args: HostArgs,
     #[command(subcommand)]
     quick_commands: QuickCommands,
 }
 
+/// Flags that control how the ghjkfile and the lockfile are loaded.
+///
+/// Their env vars take any value besides the falsey ones
+/// (`0`, `false`, `no`, `off` or empty) to mean set.
+#[derive(Debug, clap::Args)]
+struct HostArgs {
+    /// Fail if the ghjkfile needs to be serialized or the lockfile would change
+    #[arg(
+        long,
+        global = true,
+        env = "GHJK_LOCKED",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    locked: bool,
+    /// Serialize the ghjkfile even if the cached serialization is fresh
+    #[arg(
+        long,
+        global = true,
+        env = "GHJK_RE_SERIALIZE",
+        value_parser = clap::builder::FalseyValueParser::new(),
+        conflicts_with = "locked"
+    )]
+    re_serialize: bool,
+    /// Discard values resolved by the systems that were stored in the lockfile
+    #[arg(
+        long,
+        global = true,
+        env = "GHJK_RE_RESOLVE",
+        value_parser = clap::builder::FalseyValueParser::new(),
+        conflicts_with = "locked"
+    )]
+    re_resolve: bool,
+}
+
+impl HostArgs {
+    /// The host args are needed before the ghjkfile is loaded while the
+    /// rest of the CLI can only be parsed after.
+    /// This picks them out of the argv ahead of the full parse.
+    fn parse_early() -> Self {
+        use clap::{Args, FromArgMatches};
+
+        const FLAGS: [&str; 3] = ["--locked", "--re-serialize", "--re-resolve"];
+        let argv = std::env::args()
+            .skip(1)
+            .take_while(|arg| arg != "--")
+            .filter(|arg| FLAGS.contains(&arg.as_str()));
+        let cmd = Self::augment_args(clap::Command::new("ghjk").no_binary_name(true));
+        match cmd
+            .try_get_matches_from(argv)
+            .and_then(|matches| Self::from_arg_matches(&matches))
+        {
+            Ok(val) => val,
+            Err(err) => err.exit(),
+        }
+    }
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 enum CompletionShell {
     Bash,
@@ -395,6 +530,11 @@ enum QuickCommands {
         #[command(subcommand)]
         commands: init::InitCommands,
     },
+    /// Manage the lockfile
+    Lock {
+        #[command(subcommand)]
+        commands: lock::LockCommands,
+    },
     /// Access the deno cli
     Deno {
         #[arg(raw(true))]
@@ -0,0 +1,251 @@
+use crate::interlude::*;
+
+use std::process::ExitCode;
+
+use crate::config::Config;
+use crate::host::{self, migrate};
+
+#[derive(clap::Subcommand, Debug)]
+pub enum LockCommands {
+    /// Upgrade the lockfile and hashfile to the formats used by this version of ghjk
+    Migrate,
+    /// Check that the lockfile is up to date with the ghjkfile without writing it
+    ///
+    /// Exits with a non-zero code if the lockfile would change.
+    Check {
+        /// Print the report as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Three-way merge of lockfiles, intended for use as a git merge driver
+    ///
+    /// Entries changed on both sides to different values are dropped
+    /// and get resolved anew on the next ghjk invocation.
+    /// Exits with a non-zero code, leaving our version untouched,
+    /// if other parts of the lockfiles conflict.
+    Merge {
+        /// The common ancestor's version of the lockfile
+        base: PathBuf,
+        /// Our version of the lockfile, the merged lockfile is written here
+        ours: PathBuf,
+        /// Their version of the lockfile
+        theirs: PathBuf,
+    },
+}
+
+impl LockCommands {
+    pub async fn action(self, cli_config: &Config) -> Res<()> {
+        match self {
+            LockCommands::Merge { base, ours, theirs } => {
+                merge_lockfiles(&base, &ours, &theirs).await
+            }
+            LockCommands::Check { .. } => {
+                unreachable!("lock check requires loading the ghjkfile")
+            }
+            LockCommands::Migrate => {
+                let Some(ghjkdir_path) = &cli_config.ghjkdir else {
+                    eyre::bail!("no ghjkdir found");
+                };
+                let lockfile_path = ghjkdir_path.join("lock.json");
+                match migrate_file(&lockfile_path, migrate::migrate_lockfile).await? {
+                    None => println!("no lockfile found at {}", lockfile_path.display()),
+                    Some(true) => println!(
+                        "lockfile migrated to version {:?}",
+                        migrate::LOCKFILE_MIGRATIONS.current
+                    ),
+                    Some(false) => println!("lockfile already up to date"),
+                }
+                let hashfile_path = ghjkdir_path.join("hash.json");
+                match migrate_file(&hashfile_path, |raw| {
+                    migrate::HASHFILE_MIGRATIONS.migrate(raw)
+                })
+                .await?
+                {
+                    None => {}
+                    Some(true) => println!(
+                        "hashfile migrated to version {:?}",
+                        migrate::HASHFILE_MIGRATIONS.current
+                    ),
+                    Some(false) => println!("hashfile already up to date"),
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub async fn check(hcx: Arc<host::HostCtx>, ghjkdir_path: &Path, json: bool) -> Res<ExitCode> {
+    let report = host::check_lockfile(hcx, ghjkdir_path).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        match report.hashfile_stale {
+            None => println!("hashfile: missing"),
+            Some(true) => println!("hashfile: stale"),
+            Some(false) => println!("hashfile: fresh"),
+        }
+        if let Some(changes) = &report.config_modules {
+            print_changes("config modules", changes);
+        }
+        print_changes("lockfile", &report.lockfile);
+        if report.lockfile_unformatted {
+            println!("lockfile: not canonically formatted");
+        }
+    }
+    if report.has_drift() {
+        if !json {
+            eprintln!("lockfile is out of date, run ghjk to update it");
+        }
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_changes(name: &str, changes: &[host::LockChange]) {
+    if changes.is_empty() {
+        println!("{name}: up to date");
+        return;
+    }
+    println!("{name}:");
+    for change in changes {
+        let sign = match change.kind {
+            host::LockChangeKind::Added => '+',
+            host::LockChangeKind::Removed => '-',
+            host::LockChangeKind::Changed => '~',
+        };
+        println!("  {sign} {}", change.path);
+    }
+}
+
+/// Apply the migrations to the json file at the path, writing
+/// it back out if anything changed.
+///
+/// Returns `None` if the file doesn't exist.
+async fn migrate_file(
+    path: &Path,
+    migrate: impl FnOnce(&mut serde_json::Value) -> Res<bool>,
+) -> Res<Option<bool>> {
+    let raw = match tokio::fs::read(path).await {
+        Ok(val) => val,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ferr!("error reading {}: {err}", path.display())),
+    };
+    let mut raw: serde_json::Value = serde_json::from_slice(&raw)
+        .wrap_err_with(|| format!("error parsing {}", path.display()))?;
+    let changed = migrate(&mut raw)?;
+    if changed {
+        tokio::fs::write(path, crate::host::canonical_json(&raw))
+            .await
+            .wrap_err_with(|| format!("error writing {}", path.display()))?;
+    }
+    Ok(Some(changed))
+}
+
+async fn merge_lockfiles(base: &Path, ours: &Path, theirs: &Path) -> Res<()> {
+    async fn read(path: &Path) -> Res<serde_json::Value> {
+        let raw = tokio::fs::read(path)
+            .await
+            .wrap_err_with(|| format!("error reading {}", path.display()))?;
+        // git provides an empty file if there's no common ancestor
+        if raw.iter().all(|byte| byte.is_ascii_whitespace()) {
+            return Ok(serde_json::Value::Object(default()));
+        }
+        let mut raw: serde_json::Value = serde_json::from_slice(&raw)
+            .wrap_err_with(|| format!("error parsing lockfile at {}", path.display()))?;
+        migrate::migrate_lockfile(&mut raw)
+            .wrap_err_with(|| format!("error migrating lockfile at {}", path.display()))?;
+        Ok(raw)
+    }
+    let (base_val, ours_val, theirs_val) =
+        futures::try_join!(read(base), read(ours), read(theirs))?;
+
+    let mut report = MergeReport::default();
+    let merged = merge_json(
+        Some(&base_val),
+        Some(&ours_val),
+        Some(&theirs_val),
+        &[],
+        &mut report,
+    );
+    if !report.conflicts.is_empty() {
+        // our version is left as is for git to mark the conflict
+        eyre::bail!(
+            "lockfiles are not mergeable, conflicting changes found at: {}",
+            report.conflicts.join(", ")
+        );
+    }
+    let Some(merged) = merged else {
+        eyre::bail!("lockfiles are not mergeable");
+    };
+    for path in &report.dropped {
+        warn!(%path, "conflicting lockfile entry dropped, it'll be resolved anew");
+    }
+    tokio::fs::write(ours, crate::host::canonical_json(&merged))
+        .await
+        .wrap_err_with(|| format!("error writing {}", ours.display()))?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct MergeReport {
+    /// Items changed on both sides that were left out.
+    dropped: Vec<String>,
+    /// Values changed on both sides that can't be left out.
+    conflicts: Vec<String>,
+}
+
+/// Merge the lockfile values, taking the side that changed.
+///
+/// The items under the fields of the system entries, like
+/// `/sys_entries/ports/configResolutions/<hash>`, are the units of the
+/// merge. If changed on both sides, they're left out to be resolved anew.
+/// Any other value changed on both sides, like the version tags, is a conflict.
+fn merge_json(
+    base: Option<&serde_json::Value>,
+    ours: Option<&serde_json::Value>,
+    theirs: Option<&serde_json::Value>,
+    path: &[&str],
+    report: &mut MergeReport,
+) -> Option<serde_json::Value> {
+    use serde_json::Value;
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    let recurse = match path {
+        [] | ["sys_entries"] | ["sys_entries", _] => true,
+        ["sys_entries", _, "version"] => false,
+        ["sys_entries", _, _] => true,
+        ["sys_entries", _, _, _] => {
+            report.dropped.push(format!("/{}", path.join("/")));
+            return None;
+        }
+        _ => false,
+    };
+    if let (true, Some(Value::Object(ours)), Some(Value::Object(theirs))) = (recurse, ours, theirs)
+    {
+        let base = base.and_then(|val| val.as_object());
+        let merged = ours
+            .keys()
+            .chain(theirs.keys())
+            .unique()
+            .filter_map(|key| {
+                let mut path = path.to_vec();
+                path.push(key);
+                merge_json(
+                    base.and_then(|base| base.get(key)),
+                    ours.get(key),
+                    theirs.get(key),
+                    &path,
+                    report,
+                )
+                .map(|val| (key.clone(), val))
+            })
+            .collect();
+        return Some(Value::Object(merged));
+    }
+    report.conflicts.push(format!("/{}", path.join("/")));
+    ours.cloned()
+}
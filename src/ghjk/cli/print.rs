@@ -18,13 +18,19 @@ pub enum PrintCommands {
         #[arg(long)]
         json: bool, */
     },
+    /// Print why the ghjkfile will be re-serialized on the next invocation
+    Staleness {
+        /// Use json format when printing the reason
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 impl PrintCommands {
     /// The return value specifies weather or not the CLI is done or
     /// weather it should continue on with serialization if this
     /// action was invoked as part of the quick cli
-    pub fn action(
+    pub async fn action(
         self,
         cli_config: &Config,
         serialized_config: Option<&crate::host::SerializedConfig>,
@@ -59,6 +65,21 @@ impl PrintCommands {
                 }
                 None => false,
             },
+            PrintCommands::Staleness { json } => {
+                let Some(ghjkdir_path) = &cli_config.ghjkdir else {
+                    eyre::bail!("no ghjkdir found.");
+                };
+                let staleness = crate::host::hashfile_staleness(cli_config, ghjkdir_path).await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&staleness)?);
+                } else {
+                    match staleness {
+                        Some(reason) => println!("stale: {reason}"),
+                        None => println!("fresh"),
+                    }
+                }
+                true
+            }
             PrintCommands::Config {} => {
                 let conf_json = serde_json::to_string_pretty(&cli_config)?;
                 println!("{conf_json}");
@@ -1,15 +1,69 @@
 use crate::interlude::*;
 
-use crate::systems::envs::types::{Provision, ProvisionReducer, WellKnownProvision};
+use crate::systems::envs::{
+    types::{Provision, ProvisionReducer, WellKnownProvision},
+    CompletionCandidates,
+};
 
 use clap_complete::aot::{generate, Shell};
 use futures::FutureExt;
 
+/// Attach the env and task names as possible values to the args that take them.
+///
+/// This is only done on the command used for generating the scripts
+/// since the possible values would otherwise be enforced when parsing.
+fn with_candidates(root_cmd: clap::Command, candidates: &CompletionCandidates) -> clap::Command {
+    use clap::builder::PossibleValuesParser;
+
+    let env_names = candidates.env_names.clone();
+    let task_names = candidates.task_names.clone();
+    let add_values = move |cmd: clap::Command| {
+        let ids = cmd
+            .get_arguments()
+            .map(|arg| arg.get_id().to_string())
+            .collect::<Vec<_>>();
+        let mut cmd = cmd;
+        for id in ids {
+            let names = match id.as_str() {
+                "env_key" | "env" => &env_names,
+                "task_env" => &task_names,
+                _ => continue,
+            };
+            if names.is_empty() {
+                continue;
+            }
+            let names = names.clone();
+            cmd = cmd.mut_arg(id, |arg| arg.value_parser(PossibleValuesParser::new(names)));
+        }
+        cmd
+    };
+
+    let mut root_cmd = root_cmd;
+    if root_cmd.find_subcommand("sync").is_some() {
+        root_cmd = root_cmd.mut_subcommand("sync", &add_values);
+    }
+    if root_cmd.find_subcommand("envs").is_some() {
+        root_cmd = root_cmd.mut_subcommand("envs", |envs_cmd| {
+            let sub_names = envs_cmd
+                .get_subcommands()
+                .map(|cmd| cmd.get_name().to_string())
+                .collect::<Vec<_>>();
+            let mut envs_cmd = envs_cmd;
+            for name in sub_names {
+                envs_cmd = envs_cmd.mut_subcommand(name, &add_values);
+            }
+            envs_cmd
+        });
+    }
+    root_cmd
+}
+
 /// Create a reducer that expands `ghjk.cli.Completions` into pre-generated completion scripts
 pub fn ghjk_cli_completions_reducer(
     root_cmd: &clap::Command,
     sys_cmds: &[clap::Command],
     sys_actions: &IndexMap<CHeapStr, crate::cli::sys::SysCmdAction>,
+    candidates: Option<&CompletionCandidates>,
     include_aliases: bool,
 ) -> ProvisionReducer {
     let mut bash_completions = vec![];
@@ -18,6 +72,9 @@ pub fn ghjk_cli_completions_reducer(
 
     // Pre-generate scripts AOT and capture them
     let mut root_cmd = root_cmd.clone();
+    if let Some(candidates) = candidates {
+        root_cmd = with_candidates(root_cmd, candidates);
+    }
 
     bash_completions.push({
         let mut root_bash: Vec<u8> = Vec::new();
@@ -15,6 +15,39 @@ pub struct Config {
     /// - activators: embed completions into activation scripts (default)
     /// - off: disable completions generation/embedding
     pub completions: CompletionsMode,
+    /// How executables are exposed in the `shims/bin` dir of cooked envs
+    /// - symlink: link to the executables directly (default)
+    /// - wrapper: wrapper scripts that set the env's vars before exec'ing
+    pub shims: ShimsMode,
+    /// What the shell hooks do when they find the env to be stale
+    /// - off: only warn about the drift (default)
+    /// - sync: re-cook before returning to the prompt
+    /// - background: re-cook in the background
+    pub auto_sync: AutoSyncMode,
+    /// Seconds to wait on a `sync` mode re-cook before leaving it
+    /// to finish in the background
+    pub auto_sync_timeout: u64,
+    /// Patterns of env var names whose values are kept out of the
+    /// lockfile, in addition to defaults like `*_TOKEN` and `*_SECRET`
+    pub secret_env_vars: Vec<String>,
+    /// Seconds to wait on the serialization of a ghjkfile before
+    /// giving up on it
+    pub serialization_timeout: u64,
+    /// What ghjkfiles can access while being serialized
+    pub serialization_permissions: SerializationPermissions,
+}
+
+/// Scoping of the permissions ghjkfiles are serialized with.
+///
+/// Each permission is unrestricted if unset and an empty list denies it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct SerializationPermissions {
+    /// Paths that can be read
+    pub allow_read: Option<Vec<PathBuf>>,
+    /// Hosts that can be reached over the network
+    pub allow_net: Option<Vec<String>>,
+    /// Hosts that modules can be imported from
+    pub allow_import: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -24,12 +57,41 @@ pub enum CompletionsMode {
     Off,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShimsMode {
+    Symlink,
+    Wrapper,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoSyncMode {
+    Off,
+    Sync,
+    Background,
+}
+
 #[derive(Deserialize)]
 struct GlobalConfigFile {
     data_dir: Option<PathBuf>,
     deno_dir: Option<PathBuf>,
     repo_root: Option<String>,
     completions: Option<CompletionsMode>,
+    shims: Option<ShimsMode>,
+    auto_sync: Option<AutoSyncMode>,
+    auto_sync_timeout: Option<u64>,
+    secret_env_vars: Option<Vec<String>>,
+    #[serde(flatten)]
+    serialization: SerializationConfigFile,
+}
+
+#[derive(Deserialize)]
+struct SerializationConfigFile {
+    serialization_timeout: Option<u64>,
+    serialization_allow_read: Option<Vec<PathBuf>>,
+    serialization_allow_net: Option<Vec<String>>,
+    serialization_allow_import: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -57,21 +119,20 @@ impl Config {
         let ghjkfile_path = match path_from_env(&cwd, "GHJKFILE")? {
             Some(val) => Some(val),
             None => {
-                // NOTE: look for typescript ghjkfile
-                let ghjkfile_name = "ghjk.ts";
+                // NOTE: the typescript ghjkfile takes precedence
+                // over the declarative ones
+                let ghjkfile_names = ["ghjk.ts", "ghjk.toml", "ghjk.json"];
                 match &ghjkdir_path {
                     Some(ghjkfile_path) => {
-                        crate::utils::find_entry_recursive(
+                        crate::utils::find_any_entry_recursive(
                             ghjkfile_path.parent().expect_or_log("invalid GHJKDIR path"),
-                            ghjkfile_name,
+                            &ghjkfile_names,
                         )
                         .await?
                     }
-                    None => crate::utils::find_entry_recursive(&cwd, ghjkfile_name)
+                    None => crate::utils::find_any_entry_recursive(&cwd, &ghjkfile_names)
                         .await
-                        .wrap_err_with(|| {
-                            format!("error trying to locate a ghjkfile of kind \"{ghjkfile_name}\"")
-                        })?,
+                        .wrap_err("error trying to locate a ghjkfile")?,
                 }
             }
         };
@@ -105,6 +166,12 @@ impl Config {
             deno_lockfile: None,
             deno_no_lockfile: false,
             completions: CompletionsMode::Activators,
+            shims: ShimsMode::Symlink,
+            auto_sync: AutoSyncMode::Off,
+            auto_sync_timeout: 10,
+            secret_env_vars: vec![],
+            serialization_timeout: 120,
+            serialization_permissions: default(),
             repo_root: {
                 if cfg!(debug_assertions) {
                     url::Url::from_file_path(&cwd)
@@ -169,10 +236,29 @@ impl Config {
                 tokio::fs::write(
                     &ignore_path,
                     "envs
-hash.json",
+hash.json
+digests.json",
                 )
                 .await
                 .wrap_err_with(|| format!("error writing ignore file at {ignore_path:?}"))?;
+            } else {
+                // ghjkdirs created before the digest memo was
+                // introduced need it added
+                let ignores = tokio::fs::read_to_string(&ignore_path)
+                    .await
+                    .wrap_err_with(|| format!("error reading ignore file at {ignore_path:?}"))?;
+                if !ignores.lines().any(|line| line.trim() == "digests.json") {
+                    let sep = if ignores.is_empty() || ignores.ends_with('\n') {
+                        ""
+                    } else {
+                        "\n"
+                    };
+                    tokio::fs::write(&ignore_path, format!("{ignores}{sep}digests.json\n"))
+                        .await
+                        .wrap_err_with(|| {
+                            format!("error writing ignore file at {ignore_path:?}")
+                        })?;
+                }
             }
         }
         // create deno.json
@@ -189,6 +275,11 @@ hash.json",
             data_dir,
             repo_root,
             completions,
+            shims,
+            auto_sync,
+            auto_sync_timeout,
+            secret_env_vars,
+            serialization,
         } = config::Config::builder()
             .add_source(config::File::with_name(&file_path.to_string_lossy()[..]).required(false))
             .build()
@@ -214,6 +305,19 @@ hash.json",
         if let Some(mode) = completions {
             self.completions = mode;
         }
+        if let Some(mode) = shims {
+            self.shims = mode;
+        }
+        if let Some(mode) = auto_sync {
+            self.auto_sync = mode;
+        }
+        if let Some(secs) = auto_sync_timeout {
+            self.auto_sync_timeout = secs;
+        }
+        if let Some(patterns) = secret_env_vars {
+            self.secret_env_vars.extend(patterns);
+        }
+        self.source_serialization_config(serialization, parent)?;
         Ok(())
     }
 
@@ -225,6 +329,11 @@ hash.json",
                     deno_dir,
                     repo_root,
                     completions,
+                    shims,
+                    auto_sync,
+                    auto_sync_timeout,
+                    secret_env_vars,
+                    serialization,
                 },
             deno_lockfile,
             import_map,
@@ -276,6 +385,19 @@ hash.json",
         if let Some(mode) = completions {
             self.completions = mode;
         }
+        if let Some(mode) = shims {
+            self.shims = mode;
+        }
+        if let Some(mode) = auto_sync {
+            self.auto_sync = mode;
+        }
+        if let Some(secs) = auto_sync_timeout {
+            self.auto_sync_timeout = secs;
+        }
+        if let Some(patterns) = secret_env_vars {
+            self.secret_env_vars.extend(patterns);
+        }
+        self.source_serialization_config(serialization, parent)?;
         Ok(())
     }
 
@@ -287,12 +409,24 @@ hash.json",
                     deno_dir,
                     repo_root,
                     completions,
+                    shims,
+                    auto_sync,
+                    auto_sync_timeout,
+                    secret_env_vars,
+                    serialization,
                 },
             deno_lockfile,
             import_map,
             deno_json,
         } = config::Config::builder()
-            .add_source(config::Environment::with_prefix("GHJK"))
+            .add_source(
+                config::Environment::with_prefix("GHJK")
+                    .list_separator(",")
+                    .with_list_parse_key("secret_env_vars")
+                    .with_list_parse_key("serialization_allow_read")
+                    .with_list_parse_key("serialization_allow_net")
+                    .with_list_parse_key("serialization_allow_import"),
+            )
             .build()
             .wrap_err("error reading config file")?
             .try_deserialize()
@@ -333,6 +467,58 @@ hash.json",
         if let Some(mode) = completions {
             self.completions = mode;
         }
+        if let Some(mode) = shims {
+            self.shims = mode;
+        }
+        if let Some(mode) = auto_sync {
+            self.auto_sync = mode;
+        }
+        if let Some(secs) = auto_sync_timeout {
+            self.auto_sync_timeout = secs;
+        }
+        if let Some(patterns) = secret_env_vars {
+            self.secret_env_vars.extend(patterns);
+        }
+        self.source_serialization_config(serialization, cwd)?;
+        Ok(())
+    }
+
+    /// Unlike the secret env vars, later sources replace the
+    /// permission scopes of earlier ones.
+    fn source_serialization_config(
+        &mut self,
+        SerializationConfigFile {
+            serialization_timeout,
+            serialization_allow_read,
+            serialization_allow_net,
+            serialization_allow_import,
+        }: SerializationConfigFile,
+        parent: &Path,
+    ) -> Res<()> {
+        if let Some(secs) = serialization_timeout {
+            if secs == 0 {
+                eyre::bail!("serialization_timeout must be greater than zero");
+            }
+            self.serialization_timeout = secs;
+        }
+        let perms = &mut self.serialization_permissions;
+        if let Some(paths) = serialization_allow_read {
+            perms.allow_read = Some(
+                paths
+                    .iter()
+                    // empty items show up when set to an empty env var
+                    .filter(|path| !path.as_os_str().is_empty())
+                    .map(|path| resolve_config_path(path, parent))
+                    .collect::<Res<_>>()
+                    .wrap_err("error resolving serialization_allow_read")?,
+            );
+        }
+        if let Some(hosts) = serialization_allow_net {
+            perms.allow_net = Some(hosts.into_iter().filter(|host| !host.is_empty()).collect());
+        }
+        if let Some(hosts) = serialization_allow_import {
+            perms.allow_import = Some(hosts.into_iter().filter(|host| !host.is_empty()).collect());
+        }
         Ok(())
     }
 
@@ -4,19 +4,26 @@ use crate::systems::*;
 
 use std::io::IsTerminal;
 
+mod check;
+mod declarative;
 mod deno;
 mod hashfile;
+pub mod migrate;
+mod secrets;
+mod workspace;
+
+pub use check::{check_lockfile, LockChange, LockChangeKind, LockCheck};
 
 use hashfile::HashObj;
+pub use hashfile::{DigestMemo, Staleness};
 
 #[derive(Debug)]
 pub struct Config {
     /// Discard serialization cache.
     pub re_serialize: bool,
     /// Discard any resolved values in lockfile.
-    #[allow(unused)]
     pub re_resolve: bool,
-    /// Force use serialization cache.
+    /// Force use serialization cache and fail on lockfile changes.
     pub locked: bool,
     pub env_vars: IndexMap<String, String>,
     pub cwd: PathBuf,
@@ -34,6 +41,11 @@ pub struct HostCtx {
     // short amount of time. Any code, like system impls, afterwards might take
     // an unkown amount of time possibly making the hashes in this memo stale
     pub file_hash_memo: DHashMap<PathBuf, hashfile::SharedFileContentDigestFuture>,
+    /// Unlike the `file_hash_memo`, this is keyed by stat metadata
+    /// and persisted across invocations.
+    pub digest_memo: DigestMemo,
+    /// Values of env vars that are kept out of the lockfile.
+    pub secrets: secrets::Secrets,
 }
 
 impl HostCtx {
@@ -41,16 +53,79 @@ impl HostCtx {
         gcx: Arc<crate::GhjkCtx>,
         config: Config,
         systems: HashMap<SystemId, SystemManifest>,
+        digest_memo: DigestMemo,
     ) -> Self {
         Self {
+            secrets: secrets::Secrets::new(&config.env_vars, &gcx.config.secret_env_vars),
             gcx,
             config,
             systems,
             file_hash_memo: default(),
+            digest_memo,
         }
     }
 }
 
+/// Blackboard key under which the digest of the hashfile for
+/// the loaded serialization is made available to systems.
+pub const HASH_DIGEST_BB_KEY: &str = "ghjk.hashDigest";
+
+/// Digest of the hashfile in the ghjkdir if it's present and not stale.
+///
+/// This only looks at the files on disk and doesn't require
+/// a deno runtime.
+pub async fn fresh_hashfile_digest(
+    config: &crate::config::Config,
+    ghjkdir_path: &Path,
+) -> Res<Option<String>> {
+    Ok(check_hashfile(config, ghjkdir_path)
+        .await?
+        .ok()
+        .map(|hash_obj| hash_obj.digest()))
+}
+
+/// Why the ghjkfile will be re-serialized on the next invocation,
+/// `None` if the hashfile in the ghjkdir is fresh.
+///
+/// Like [`fresh_hashfile_digest`], this doesn't require a deno runtime.
+pub async fn hashfile_staleness(
+    config: &crate::config::Config,
+    ghjkdir_path: &Path,
+) -> Res<Option<Staleness>> {
+    Ok(check_hashfile(config, ghjkdir_path).await?.err())
+}
+
+async fn check_hashfile(
+    config: &crate::config::Config,
+    ghjkdir_path: &Path,
+) -> Res<Result<HashObj, Staleness>> {
+    let hash_obj = match HashObj::from_file(&ghjkdir_path.join("hash.json")).await {
+        Ok(Some(val)) => val,
+        Ok(None) => return Ok(Err(Staleness::Missing)),
+        Err(hashfile::HashfileError::Serialization(_)) => return Ok(Err(Staleness::Corrupt)),
+        Err(hashfile::HashfileError::Other(err)) => return Err(err),
+    };
+    if hash_obj.config.is_none() {
+        return Ok(Err(Staleness::NoConfig));
+    }
+    let env_vars = std::env::vars().collect();
+    let cwd = std::env::current_dir()?;
+    let digest_memo = DigestMemo::load(ghjkdir_path).await;
+    let dcx = hashfile::DigestCx {
+        cli_config: config,
+        env_vars: &env_vars,
+        cwd: &cwd,
+        file_hash_memo: &default(),
+        digest_memo: &digest_memo,
+    };
+    let staleness = hash_obj.staleness(&dcx).await?;
+    digest_memo.persist().await?;
+    if let Some(reason) = staleness {
+        return Ok(Err(reason));
+    }
+    Ok(Ok(hash_obj))
+}
+
 #[tracing::instrument(skip(hcx))]
 pub async fn systems_from_ghjkfile(
     hcx: Arc<HostCtx>,
@@ -69,7 +144,7 @@ pub async fn systems_from_ghjkfile(
     );
 
     // discard corrupt files if needed
-    let (mut hash_obj, mut lock_obj) = (
+    let (mut hash_obj, lock_obj) = (
         match hash_obj {
             Ok(val) => val,
             Err(hashfile::HashfileError::Serialization(_)) => {
@@ -126,7 +201,7 @@ pub async fn systems_from_ghjkfile(
         (
             crate::utils::file_exists(path).await?,
             Some(
-                hashfile::file_digest_hash(hcx.as_ref(), path)
+                hashfile::file_digest_hash(&hcx.digest_cx(), path)
                     .await?
                     .unwrap(),
             ),
@@ -137,72 +212,81 @@ pub async fn systems_from_ghjkfile(
 
     // check if we need to discard the hashfile
     if let Some(obj) = &mut hash_obj {
-        // NOTE: version migrator would go here
-        if obj.version != "0" {
-            eyre::bail!("unsupported hashfile version: {:?}", obj.version);
-        }
-        if !hcx.config.locked
-            && (hcx.config.re_serialize
-                // no need for expensive staleness checks if the ghjkfile
-                // no longer exists
-                || ghjkfile_hash.is_none()
-                || obj
-                    .is_stale(hcx.as_ref())
-                    .await
-                    .inspect(|is_stale| {
-                        if *is_stale {
-                            debug!("stale hashfile, discarding")
-                        }
-                    })?)
+        if hcx.config.locked {
+            if obj.config.is_none() {
+                eyre::bail!("locked flag is set but the hashfile has no serialized config");
+            }
+            if ghjkfile_hash.is_some() {
+                if let Some(reason) = obj.staleness(&hcx.digest_cx()).await? {
+                    eyre::bail!(
+                        "locked flag is set but the ghjkfile changed since its serialization: {reason}"
+                    );
+                }
+            }
+        } else if hcx.config.re_serialize
+            // hashfiles migrated from older versions don't
+            // have the config
+            || obj.config.is_none()
+            // no need for expensive staleness checks if the ghjkfile
+            // no longer exists
+            || ghjkfile_hash.is_none()
+            || obj
+                .staleness(&hcx.digest_cx())
+                .await?
+                .inspect(|reason| debug!(%reason, "stale hashfile, discarding"))
+                .is_some()
         {
             hash_obj = None;
         }
     }
-    // check if we need to discard the lockfile
-    if let Some(obj) = &mut lock_obj {
-        // TODO: version migrator
-        if obj.version != "0" {
-            eyre::bail!("unsupported lockfile version: {:?}", obj.version);
-        }
-    }
-
     let mut lock_entries = HashMap::new();
 
     let scx_first = Arc::new(SystemsCtx::new());
 
-    if let Some(lock_obj) = &mut lock_obj {
+    if let Some(lock_obj) = &lock_obj {
         debug!(?lockfile_path, "loading lockfile");
-        for sys_conf in &lock_obj.config.modules {
-            let Some(sys_man) = hcx.systems.get(&sys_conf.id) else {
-                eyre::bail!(
-                    "unrecognized system found in lockfile config: {:?}",
-                    sys_conf.id
-                );
+        for (sys_id, sys_lock) in &lock_obj.sys_entries {
+            let Some(sys_man) = hcx.systems.get(sys_id) else {
+                eyre::bail!("unrecognized system found in lockfile: {sys_id:?}");
             };
-            let Some(sys_lock) = lock_obj.sys_entries.swap_remove(&sys_conf.id) else {
-                eyre::bail!(
-                    "no lock entry found for system specified by lockfile config: {:?}",
-                    sys_conf.id
+            if hcx.config.re_resolve {
+                // systems resolve anew when not given their lock entries
+                debug!(?sys_id, "re_resolve flag set, discarding lock entry");
+                continue;
+            }
+            let found = hcx.secrets.find(sys_lock);
+            if !found.is_empty() {
+                if hcx.config.locked {
+                    eyre::bail!(
+                        "locked flag is set but the lock entry of system {sys_id:?} \
+                        contains values of secret env vars: {}",
+                        found.join(", ")
+                    );
+                }
+                warn!(
+                    ?sys_id,
+                    "lock entry contains values of secret env vars, they'll be scrubbed \
+                    on the next write: {}",
+                    found.join(", ")
                 );
-            };
+            }
+            let mut sys_lock = sys_lock.clone();
+            hcx.secrets.restore(&mut sys_lock);
             let sys_inst = sys_man.init(scx_first.clone()).await?;
-            lock_entries.insert(
-                sys_conf.id.clone(),
-                sys_inst.load_lock_entry(sys_lock).await?,
-            );
+            lock_entries.insert(sys_id.clone(), sys_inst.load_lock_entry(sys_lock).await?);
         }
     }
 
     let mut fresh_serialized = false;
 
-    let (config, hash_obj) = if let (Some(lock_obj), Some(hash_obj)) = (&lock_obj, hash_obj) {
-        // Only recover the old config if the hash_obj and lock_obj haven't
+    let (config, hash_obj) = if let Some((config, hash_obj)) =
+        hash_obj.and_then(|hash_obj| hash_obj.config.clone().map(|config| (config, hash_obj)))
+    {
+        // Only recover the old config if the hash_obj hasn't
         // been discarded by the cache invalidation checks above.
-        // Assumes that a hashfile tags the specific serialized version of the ghjkfile
-        // and it's context put in the lockfile
-        (lock_obj.config.clone(), hash_obj)
+        (config, hash_obj)
     } else if avoid_serialization {
-        // we avoid serialization if unable to recover a non-stale lock obj
+        // we avoid serialization if unable to recover a non-stale hash obj
         return Ok(None);
     } else if let Some(ghjkfile_path) = &hcx.gcx.config.ghjkfile {
         if !ghjkfile_exists {
@@ -213,7 +297,6 @@ pub async fn systems_from_ghjkfile(
         }
         info!(?ghjkfile_path, "serializing ghjkfile");
         fresh_serialized = true;
-        // TODO: configurable timeout on serialization
         serialize_ghjkfile(hcx.as_ref(), ghjkfile_path)
             .await
             .wrap_err("error serializing ghjkfile")?
@@ -233,6 +316,7 @@ pub async fn systems_from_ghjkfile(
         // all state must go through the lock entries generated
         // above
         let scx_second = Arc::new(SystemsCtx::new());
+        scx_second.insert_bb(HASH_DIGEST_BB_KEY, Arc::new(hash_obj.digest()));
         for sys_conf in &config.modules {
             let Some(sys_man) = hcx.systems.get(&sys_conf.id) else {
                 eyre::bail!(
@@ -286,11 +370,10 @@ impl GhjkfileSystems {
         }
     }
 
-    #[tracing::instrument(skip(self))]
-    pub async fn write_lockfile(&mut self) -> Res<()> {
+    /// The lockfile as it'd be written out.
+    pub async fn gen_lock_obj(&mut self) -> Res<LockObj> {
         let mut lock_obj = LockObj {
-            version: "0".into(),
-            config: self.config.clone(),
+            version: migrate::LOCKFILE_MIGRATIONS.current.into(),
             sys_entries: default(),
         };
         // generate the lock entries after *all* the systems
@@ -298,25 +381,36 @@ impl GhjkfileSystems {
         // any shared stores to be properly populated
         // e.g. the resolution memo store
         for (sys_id, sys_inst) in &mut self.sys_instances {
-            let lock_entry = sys_inst.gen_lock_entry().await.wrap_err_with(|| {
+            let mut lock_entry = sys_inst.gen_lock_entry().await.wrap_err_with(|| {
                 format!("error generating lock entry for system: {:?}", sys_id)
             })?;
+            let scrubbed = self.hcx.secrets.scrub(&mut lock_entry);
+            if !scrubbed.is_empty() {
+                debug!(
+                    ?sys_id,
+                    ?scrubbed,
+                    "secret env vars scrubbed from lock entry"
+                );
+            }
             lock_obj.sys_entries.insert(sys_id.clone(), lock_entry);
         }
+        Ok(lock_obj)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn write_lockfile(&mut self) -> Res<()> {
+        let lock_obj = self.gen_lock_obj().await?;
 
         if self.old_lock_obj.is_none()
             || matches!(self.old_lock_obj.as_ref(), Some(old) if !old.eq(&lock_obj))
         {
             if self.hcx.config.locked {
-                warn!("locked flag set, changes to lockfile discarded");
+                eyre::bail!("locked flag is set but the lockfile would change");
             } else {
                 trace!(lockfile_path = ?self.lockfile_path, /* ?lock_obj, */ "writing lock.json");
-                tokio::fs::write(
-                    &self.lockfile_path,
-                    serde_json::to_vec_pretty(&lock_obj).expect_or_log("error jsonifying lockfile"),
-                )
-                .await
-                .wrap_err("error writing to lockfile")?;
+                tokio::fs::write(&self.lockfile_path, lock_obj.to_canonical_json())
+                    .await
+                    .wrap_err("error writing to lockfile")?;
                 self.old_lock_obj.replace(lock_obj);
             }
         }
@@ -337,22 +431,37 @@ impl GhjkfileSystems {
             .wrap_err("error writing to lockfile")?;
             self.hashfile_written = true;
         }
+        self.hcx.digest_memo.persist().await?;
         Ok(())
     }
 }
 
 async fn serialize_ghjkfile(hcx: &HostCtx, path: &Path) -> Res<(Arc<SerializedConfig>, HashObj)> {
-    let ext = path.extension();
-    let res = if ext.map(|ext| ext == "ts" || ext == "js") == Some(true) {
-        deno::serialize_deno_ghjkfile(hcx, path).await?
-    } else {
-        eyre::bail!("unrecognized ghjkfile extension: {path:?}")
-    };
+    let mut res = serialize_single_ghjkfile(hcx, path).await?;
+    if !res.config.members.is_empty() {
+        workspace::add_members(hcx, path, &mut res)
+            .await
+            .wrap_err("error loading workspace members")?;
+    }
     debug!("ghjkfile serialized");
-    let hash_obj = HashObj::from_result(hcx, path, &res)
+    let mut hash_obj = HashObj::from_result(hcx, path, &res)
         .await
         .wrap_err("error building hash obj")?;
-    Ok((Arc::new(res.config), hash_obj))
+    let config = Arc::new(res.config);
+    hash_obj.config = Some(config.clone());
+    Ok((config, hash_obj))
+}
+
+/// Serialize the ghjkfile without loading any of its members.
+async fn serialize_single_ghjkfile(hcx: &HostCtx, path: &Path) -> Res<SerializationResult> {
+    let ext = path.extension();
+    if ext.map(|ext| ext == "ts" || ext == "js") == Some(true) {
+        deno::serialize_deno_ghjkfile(hcx, path).await
+    } else if ext.map(|ext| ext == "toml" || ext == "json") == Some(true) {
+        declarative::serialize_declarative_ghjkfile(hcx, path).await
+    } else {
+        eyre::bail!("unrecognized ghjkfile extension: {path:?}")
+    }
 }
 
 #[derive(Debug)]
@@ -361,19 +470,25 @@ struct SerializationResult {
     accessed_env_keys: Vec<String>,
     read_file_paths: Vec<PathBuf>,
     listed_file_paths: Vec<PathBuf>,
+    /// Glob patterns whose matches were used in the config.
+    glob_patterns: Vec<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct SerializedConfig {
     modules: Vec<SystemConfig>,
     blackboard: ConfigBlackboard,
+    /// Directories of the workspace's members, see [`workspace`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    members: Vec<String>,
 }
 
+/// The lockfile only holds values resolved by the systems, the
+/// serialized config is cached in the hashfile which isn't checked in.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct LockObj {
     pub version: String,
     pub sys_entries: indexmap::IndexMap<CHeapStr, serde_json::Value>,
-    pub config: Arc<SerializedConfig>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -385,15 +500,45 @@ pub enum LockfileError {
 }
 
 impl LockObj {
-    /// The lock.json file stores the serialized config and some entries
-    /// from systems. It's primary purpose is as a memo store to avoid
-    /// re-serialization on each CLI invocation.
+    /// The lock.json file stores the entries from systems. It's primary
+    /// purpose is to persist values resolved by the systems like port versions.
     pub async fn from_file(path: &Path) -> Result<Option<Self>, LockfileError> {
         let raw = match tokio::fs::read(path).await {
             Ok(val) => val,
             Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
             Err(err) => return Err(LockfileError::Other(ferr!("error reading lockfile: {err}"))),
         };
-        serde_json::from_slice(&raw).map_err(LockfileError::Serialization)
+        let mut raw: serde_json::Value =
+            serde_json::from_slice(&raw).map_err(LockfileError::Serialization)?;
+        migrate::LOCKFILE_MIGRATIONS.migrate(&mut raw)?;
+        serde_json::from_value(raw).map_err(LockfileError::Serialization)
+    }
+
+    pub fn to_canonical_json(&self) -> Vec<u8> {
+        canonical_json(&serde_json::to_value(self).expect_or_log("error jsonifying lockfile"))
+    }
+}
+
+/// Pretty json with the keys of all objects sorted.
+///
+/// The output only depends on the contents which keeps the
+/// diffs of files checked into version control minimal.
+pub fn canonical_json(val: &serde_json::Value) -> Vec<u8> {
+    fn sorted(val: &serde_json::Value) -> serde_json::Value {
+        match val {
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.iter()
+                    .sorted_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b))
+                    .map(|(key, val)| (key.clone(), sorted(val)))
+                    .collect(),
+            ),
+            serde_json::Value::Array(arr) => {
+                serde_json::Value::Array(arr.iter().map(sorted).collect())
+            }
+            val => val.clone(),
+        }
     }
+    let mut out = serde_json::to_vec_pretty(&sorted(val)).expect_or_log("error jsonifying");
+    out.push(b'\n');
+    out
 }
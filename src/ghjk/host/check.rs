@@ -0,0 +1,152 @@
+//! Checking whether the lockfile on disk is up to date with the ghjkfile.
+
+use crate::interlude::*;
+
+use super::{canonical_json, hashfile, systems_from_ghjkfile, HostCtx};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockCheck {
+    /// If the hashfile was stale, `None` if there was no hashfile.
+    pub hashfile_stale: Option<bool>,
+    /// Changes to the config modules since the cached serialization,
+    /// `None` if there was no cached serialization to compare against.
+    pub config_modules: Option<Vec<LockChange>>,
+    /// Changes the lockfile would see if written out.
+    pub lockfile: Vec<LockChange>,
+    /// If the lockfile's contents are up to date but it's
+    /// not in the canonical formatting. This doesn't count
+    /// as drift since ghjk only rewrites it on changes.
+    pub lockfile_unformatted: bool,
+}
+
+impl LockCheck {
+    pub fn has_drift(&self) -> bool {
+        !self.lockfile.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockChange {
+    pub path: String,
+    pub kind: LockChangeKind,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum LockChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Serialize the ghjkfile anew and compare the lockfile that'd result
+/// against the one on disk without writing anything.
+///
+/// The host config is expected to have `re_serialize` set.
+pub async fn check_lockfile(hcx: Arc<HostCtx>, ghjkdir_path: &Path) -> Res<LockCheck> {
+    let lockfile_path = ghjkdir_path.join("lock.json");
+
+    let hash_obj = match hashfile::HashObj::from_file(&ghjkdir_path.join("hash.json")).await {
+        Ok(val) => val,
+        Err(hashfile::HashfileError::Serialization(_)) => None,
+        Err(hashfile::HashfileError::Other(err)) => return Err(err),
+    };
+    let hashfile_stale = match &hash_obj {
+        Some(obj) => Some(obj.is_stale(&hcx.digest_cx()).await?),
+        None => None,
+    };
+    let cached_config = hash_obj.and_then(|obj| obj.config);
+
+    // read without migration so that older formats show up as drift
+    let committed_raw = match tokio::fs::read(&lockfile_path).await {
+        Ok(val) => Some(val),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(ferr!("error reading lockfile: {err}")),
+    };
+    let committed = committed_raw
+        .as_ref()
+        .map(|raw| serde_json::from_slice::<serde_json::Value>(raw))
+        .transpose()
+        .wrap_err("error parsing lockfile")?;
+
+    let Some(mut systems) = systems_from_ghjkfile(hcx, ghjkdir_path, false).await? else {
+        eyre::bail!("no ghjkfile found");
+    };
+    let fresh = serde_json::to_value(systems.gen_lock_obj().await?)?;
+
+    let mut lockfile = vec![];
+    json_changes(committed.as_ref(), Some(&fresh), "", &mut lockfile);
+    let lockfile_unformatted =
+        lockfile.is_empty() && committed_raw.is_some_and(|raw| raw != canonical_json(&fresh));
+
+    let config_modules = cached_config.map(|cached| {
+        let mut changes = vec![];
+        for module in &cached.modules {
+            match systems
+                .config
+                .modules
+                .iter()
+                .find(|cur| cur.id == module.id)
+            {
+                None => changes.push(LockChange {
+                    path: module.id.to_string(),
+                    kind: LockChangeKind::Removed,
+                }),
+                Some(cur) if cur != module => changes.push(LockChange {
+                    path: module.id.to_string(),
+                    kind: LockChangeKind::Changed,
+                }),
+                Some(_) => {}
+            }
+        }
+        for module in &systems.config.modules {
+            if !cached.modules.iter().any(|cached| cached.id == module.id) {
+                changes.push(LockChange {
+                    path: module.id.to_string(),
+                    kind: LockChangeKind::Added,
+                });
+            }
+        }
+        changes
+    });
+
+    Ok(LockCheck {
+        hashfile_stale,
+        config_modules,
+        lockfile,
+        lockfile_unformatted,
+    })
+}
+
+/// Record the paths of the leaves that differ between the values.
+fn json_changes(
+    before: Option<&serde_json::Value>,
+    after: Option<&serde_json::Value>,
+    path: &str,
+    out: &mut Vec<LockChange>,
+) {
+    use serde_json::Value;
+    match (before, after) {
+        (before, after) if before == after => {}
+        (Some(Value::Object(before)), Some(Value::Object(after))) => {
+            for key in before.keys().chain(after.keys()).unique().sorted() {
+                json_changes(
+                    before.get(key),
+                    after.get(key),
+                    &format!("{path}/{key}"),
+                    out,
+                );
+            }
+        }
+        (before, after) => out.push(LockChange {
+            path: path.to_string(),
+            kind: match (before, after) {
+                (None, _) => LockChangeKind::Added,
+                (_, None) => LockChangeKind::Removed,
+                _ => LockChangeKind::Changed,
+            },
+        }),
+    }
+}
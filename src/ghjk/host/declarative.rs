@@ -0,0 +1,377 @@
+//! Serialization of ghjkfiles written in a declarative format.
+//!
+//! `ghjk.toml` and `ghjk.json` files are parsed here directly into
+//! the config modules without starting up deno. They only support
+//! a subset of what `ghjk.ts` files can express: envs with static
+//! and dynamic vars, port installs and tasks that run commands.
+
+use crate::interlude::*;
+
+use crate::systems::envs::types::{
+    DynEnvVarProvision, DynEnvVarSource, EnvRecipe, EnvsModuleConfig, GitField, Provision,
+    WellKnownProvision,
+};
+use crate::systems::tasks::types::{CmdTaskDefHashed, TaskDefHashed, TasksModuleConfig};
+use crate::systems::SystemConfig;
+
+const DEFAULT_ENV: &str = "main";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct DeclarativeGhjkfile {
+    /// The env activated when none is specified, `main` by default
+    #[serde(default)]
+    default_env: Option<String>,
+    #[serde(default)]
+    envs: IndexMap<String, EnvDecl>,
+    #[serde(default)]
+    tasks: IndexMap<String, TaskDecl>,
+    /// Directories of workspace members
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct EnvDecl {
+    desc: Option<String>,
+    /// Envs to inherit from, the default env if not set
+    inherit: Option<InheritDecl>,
+    #[serde(default)]
+    vars: IndexMap<String, VarDecl>,
+    #[serde(default)]
+    installs: Vec<InstallDecl>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TaskDecl {
+    desc: Option<String>,
+    /// The program and its arguments
+    cmd: Vec<String>,
+    /// Relative to the ghjkfile's dir
+    working_dir: Option<String>,
+    depends_on: Option<Vec<String>>,
+    env_hooks: Option<bool>,
+    /// Envs the task env inherits from, the default env if not set
+    inherit: Option<InheritDecl>,
+    #[serde(default)]
+    vars: IndexMap<String, VarDecl>,
+    #[serde(default)]
+    installs: Vec<InstallDecl>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum InheritDecl {
+    /// `false` to inherit nothing, `true` for the default env
+    Toggle(bool),
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged, deny_unknown_fields)]
+enum VarDecl {
+    Static(String),
+    Cmd { cmd: Vec<String> },
+    File { file: String },
+    Git { git: GitField },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct InstallDecl {
+    /// Name of the port module in ghjk's `ports` dir
+    port: String,
+    /// Rest of the install config passed to the port module
+    #[serde(flatten)]
+    config: serde_json::Map<String, serde_json::Value>,
+}
+
+/// An env declaration with the declarations it inherits merged in.
+#[derive(Debug, Default, Clone)]
+struct FlatEnv {
+    vars: IndexMap<String, VarDecl>,
+    installs: Vec<InstallDecl>,
+}
+
+#[tracing::instrument(skip(_hcx))]
+pub async fn serialize_declarative_ghjkfile(
+    _hcx: &super::HostCtx,
+    path: &Path,
+) -> Res<super::SerializationResult> {
+    let raw = tokio::fs::read_to_string(path)
+        .await
+        .wrap_err_with(|| format!("error reading ghjkfile at {path:?}"))?;
+    let file: DeclarativeGhjkfile = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => {
+            toml::from_str(&raw).wrap_err_with(|| format!("error parsing ghjkfile at {path:?}"))?
+        }
+        Some("json") => {
+            let val = jsonc_parser::parse_to_serde_value(&raw, &default())
+                .wrap_err_with(|| format!("error parsing ghjkfile at {path:?}"))?
+                .unwrap_or_else(|| serde_json::Value::Object(default()));
+            serde_json::from_value(val)
+                .wrap_err_with(|| format!("error parsing ghjkfile at {path:?}"))?
+        }
+        _ => eyre::bail!("unrecognized declarative ghjkfile extension: {path:?}"),
+    };
+
+    let config = file.into_config()?;
+
+    debug!("declarative ghjkfile serialized");
+
+    Ok(super::SerializationResult {
+        config,
+        accessed_env_keys: vec![],
+        listed_file_paths: vec![],
+        glob_patterns: vec![],
+        read_file_paths: vec![path.to_owned()],
+    })
+}
+
+impl DeclarativeGhjkfile {
+    fn into_config(mut self) -> Res<super::SerializedConfig> {
+        let default_env = self
+            .default_env
+            .take()
+            .unwrap_or_else(|| DEFAULT_ENV.to_string());
+        // make sure the default env exists
+        self.envs.entry(default_env.clone()).or_default();
+        let members = std::mem::take(&mut self.members);
+
+        for (name, task) in &self.tasks {
+            for dep in task.depends_on.iter().flatten() {
+                if !self.tasks.contains_key(dep) {
+                    eyre::bail!("task '{name}' depends on unknown task '{dep}'");
+                }
+            }
+        }
+
+        let mut flattened = IndexMap::new();
+        for name in self.envs.keys() {
+            self.flatten_env(name, &default_env, &mut flattened, &mut vec![])?;
+        }
+
+        let mut blackboard = serde_json::Map::new();
+        let mut add_to_bb = |val: serde_json::Value| {
+            let hash = crate::utils::hash_obj(&val);
+            blackboard.insert(hash.clone(), val);
+            hash
+        };
+        let no_build_deps = add_to_bb(json!({}));
+
+        let mut sets = serde_json::Map::new();
+        let mut envs = IndexMap::new();
+        let mut envs_named = IndexMap::new();
+        // envs are keyed by the hash of their recipe like in `ghjk.ts` files,
+        // the key is used for the env's dir and the names link to it
+        let mut recipe = |set_id: &str, desc: Option<String>, env: &FlatEnv| {
+            let mut provides = env
+                .vars
+                .iter()
+                .map(|(key, val)| var_provision(key, val))
+                .collect::<Res<Vec<_>>>()?;
+            provides.push(Provision::Strange(json!({ "ty": "ghjk.tasks.Alias" })));
+            provides.push(Provision::Strange(json!({ "ty": "ghjk.cli.Completions" })));
+            if !env.installs.is_empty() {
+                let installs = env
+                    .installs
+                    .iter()
+                    .map(|inst| {
+                        let mut config = inst.config.clone();
+                        config.insert("portModule".into(), inst.port.clone().into());
+                        add_to_bb(serde_json::Value::Object(config))
+                    })
+                    .collect::<Vec<_>>();
+                sets.insert(
+                    set_id.to_string(),
+                    json!({
+                        "installs": installs,
+                        "allowedBuildDeps": no_build_deps,
+                        "stdDeps": true,
+                    }),
+                );
+                provides.push(Provision::Strange(json!({
+                    "ty": "ghjk.ports.InstallSetRef",
+                    "setId": set_id,
+                })));
+            }
+            let recipe = EnvRecipe {
+                desc,
+                provides,
+                provenance: None,
+            };
+            let env_key = crate::utils::hash_obj(&recipe);
+            envs.entry(env_key.clone()).or_insert(recipe);
+            Res::Ok(env_key)
+        };
+
+        for (name, env) in &self.envs {
+            let env_key = recipe(name, env.desc.clone(), &flattened[name])?;
+            envs_named.insert(name.clone(), env_key);
+        }
+
+        let mut tasks = IndexMap::new();
+        for (name, task) in self.tasks {
+            let mut env = match &task.inherit {
+                None => flattened[&default_env].clone(),
+                Some(inherit) => {
+                    let mut env = FlatEnv::default();
+                    for parent in inherit_names(inherit, &default_env) {
+                        let Some(parent) = flattened.get(parent) else {
+                            eyre::bail!("task '{name}' inherits from unknown env '{parent}'");
+                        };
+                        env.merge(parent);
+                    }
+                    env
+                }
+            };
+            env.merge(&FlatEnv {
+                vars: task.vars,
+                installs: task.installs,
+            });
+            let env_key = recipe(&format!("____task_env_{name}"), None, &env)?;
+            tasks.insert(
+                name,
+                TaskDefHashed::CmdV1(CmdTaskDefHashed {
+                    desc: task.desc,
+                    working_dir: task.working_dir,
+                    depends_on: task.depends_on,
+                    env_hooks: task.env_hooks,
+                    env_key,
+                    cmd: task.cmd,
+                    ghjkfile: None,
+                }),
+            );
+        }
+        let tasks_named = tasks.keys().cloned().collect();
+
+        Ok(super::SerializedConfig {
+            modules: vec![
+                SystemConfig {
+                    id: "ports".into(),
+                    config: json!({ "sets": sets }),
+                },
+                SystemConfig {
+                    id: "tasks".into(),
+                    config: serde_json::to_value(TasksModuleConfig {
+                        tasks,
+                        tasks_named,
+                        members: vec![],
+                    })?,
+                },
+                SystemConfig {
+                    id: "envs".into(),
+                    config: serde_json::to_value(EnvsModuleConfig {
+                        default_env,
+                        envs,
+                        envs_named,
+                    })?,
+                },
+            ],
+            blackboard: Arc::new(blackboard),
+            members,
+        })
+    }
+
+    fn flatten_env(
+        &self,
+        name: &str,
+        default_env: &str,
+        flattened: &mut IndexMap<String, FlatEnv>,
+        stack: &mut Vec<String>,
+    ) -> Res<()> {
+        if flattened.contains_key(name) {
+            return Ok(());
+        }
+        if stack.iter().any(|item| item == name) {
+            eyre::bail!(
+                "cyclic env inheritance found: {} -> {name}",
+                stack.join(" -> ")
+            );
+        }
+        let Some(decl) = self.envs.get(name) else {
+            eyre::bail!(
+                "env '{}' inherits from unknown env '{name}'",
+                stack.last().map(|name| &name[..]).unwrap_or_default()
+            );
+        };
+        let parents = match &decl.inherit {
+            // the default env doesn't implicitly inherit from itself
+            None if name == default_env => vec![],
+            None => vec![default_env],
+            Some(inherit) => inherit_names(inherit, default_env),
+        };
+        stack.push(name.to_string());
+        let mut env = FlatEnv::default();
+        for parent in parents {
+            self.flatten_env(parent, default_env, flattened, stack)?;
+            env.merge(&flattened[parent]);
+        }
+        stack.pop();
+        env.merge(&FlatEnv {
+            vars: decl.vars.clone(),
+            installs: decl.installs.clone(),
+        });
+        flattened.insert(name.to_string(), env);
+        Ok(())
+    }
+}
+
+impl FlatEnv {
+    /// Merge in the other env, its vars taking precedence and
+    /// its installs replacing any of the same port.
+    fn merge(&mut self, other: &FlatEnv) {
+        for (key, val) in &other.vars {
+            self.vars.insert(key.clone(), val.clone());
+        }
+        for inst in &other.installs {
+            self.installs.retain(|cur| cur.port != inst.port);
+            self.installs.push(inst.clone());
+        }
+    }
+}
+
+fn inherit_names<'a>(inherit: &'a InheritDecl, default_env: &'a str) -> Vec<&'a str> {
+    match inherit {
+        InheritDecl::Toggle(false) => vec![],
+        InheritDecl::Toggle(true) => vec![default_env],
+        InheritDecl::One(name) => vec![&name[..]],
+        InheritDecl::Many(names) => names.iter().map(|name| &name[..]).collect(),
+    }
+}
+
+fn var_provision(key: &str, val: &VarDecl) -> Res<Provision> {
+    let source = match val {
+        VarDecl::Static(val) => {
+            return Ok(Provision::WellKnown(WellKnownProvision::PosixEnvVar {
+                key: key.to_string(),
+                val: val.clone(),
+                merge: None,
+            }))
+        }
+        VarDecl::Cmd { cmd } => {
+            let Some((program, arguments)) = cmd.split_first() else {
+                eyre::bail!("env var '{key}' has an empty cmd");
+            };
+            DynEnvVarSource::Cmd {
+                program: program.clone(),
+                arguments: arguments.to_vec(),
+                working_dir: None,
+            }
+        }
+        VarDecl::File { file } => DynEnvVarSource::File { path: file.clone() },
+        VarDecl::Git { git } => DynEnvVarSource::Git {
+            field: *git,
+            working_dir: None,
+        },
+    };
+    Ok(Provision::Strange(serde_json::to_value(
+        DynEnvVarProvision {
+            key: key.to_string(),
+            source,
+        },
+    )?))
+}
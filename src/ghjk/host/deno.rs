@@ -2,6 +2,9 @@ use crate::interlude::*;
 
 use denort::deno::deno_runtime;
 
+/// Hosts ghjk's own typescript sources import from.
+const GHJK_IMPORT_HOSTS: &[&str] = &["deno.land", "jsr.io"];
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct InternalSerializationResult {
@@ -46,20 +49,32 @@ pub async fn serialize_deno_ghjkfile(
         .deno
         .prepare_module(
             main_module.clone(),
-            deno_runtime::deno_permissions::PermissionsOptions {
-                allow_env: Some(vec![]),
-                allow_import: Some(vec![]),
-                allow_read: Some(vec![]),
-                allow_net: Some(vec![]),
-                ..default()
-            },
+            permissions(hcx, path),
             deno_runtime::WorkerExecutionMode::Run,
             default(),
             Some(crate::ext::extensions(ext_conf)),
         )
         .await?;
 
-    let (exit_code, mut worker) = worker.run().await?;
+    let timeout = std::time::Duration::from_secs(hcx.gcx.config.serialization_timeout);
+    let (exit_code, mut worker) = match tokio::time::timeout(timeout, worker.run()).await {
+        Ok(res) => res?,
+        Err(_) => {
+            // there's no stopping a single module worker so we bring down
+            // the whole runtime, it's of no use after a failed serialization
+            hcx.gcx
+                .deno
+                .clone()
+                .terminate()
+                .await
+                .wrap_err("error terminating deno runtime")?;
+            eyre::bail!(
+                "ghjkfile serialization timed out after {}s, \
+                see the serialization_timeout config",
+                timeout.as_secs()
+            );
+        }
+    };
     if exit_code != 0 {
         eyre::bail!("non-zero exit code running deno module");
     }
@@ -87,6 +102,55 @@ pub async fn serialize_deno_ghjkfile(
         config: resp.config,
         accessed_env_keys: resp.accessed_env_keys,
         listed_file_paths: resp.listed_file_paths,
+        glob_patterns: vec![],
         read_file_paths,
     })
 }
+
+/// Translate the configured scopes to deno's permission options where
+/// an empty list allows everything and `None` denies.
+fn permissions(
+    hcx: &super::HostCtx,
+    ghjkfile_path: &Path,
+) -> deno_runtime::deno_permissions::PermissionsOptions {
+    let config = &hcx.gcx.config;
+    let scoped = &config.serialization_permissions;
+    let scope = |allowed: &Option<Vec<String>>, implied: Vec<String>| match allowed {
+        None => Some(vec![]),
+        Some(allowed) if allowed.is_empty() => None,
+        Some(allowed) => Some(allowed.iter().cloned().chain(implied).collect()),
+    };
+    // ghjk's sources are also at the repo root
+    let repo_root = match config.repo_root.scheme() {
+        "file" => config.repo_root.to_file_path().ok(),
+        _ => None,
+    };
+    let implied_read = ghjkfile_path
+        .parent()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .chain(config.ghjkdir.clone())
+        .chain(repo_root)
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    let implied_import = GHJK_IMPORT_HOSTS
+        .iter()
+        .map(|host| host.to_string())
+        .chain(config.repo_root.host_str().map(String::from))
+        .collect();
+    deno_runtime::deno_permissions::PermissionsOptions {
+        allow_env: Some(vec![]),
+        allow_read: scope(
+            &scoped.allow_read.as_ref().map(|paths| {
+                paths
+                    .iter()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect()
+            }),
+            implied_read,
+        ),
+        allow_net: scope(&scoped.allow_net, vec![]),
+        allow_import: scope(&scoped.allow_import, implied_import),
+        ..default()
+    }
+}
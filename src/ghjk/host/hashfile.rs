@@ -2,6 +2,30 @@ use crate::interlude::*;
 
 use super::HostCtx;
 
+/// What's needed to compute the digests tracked in a hashfile.
+///
+/// This doesn't require a full [`HostCtx`] in order to support
+/// checks that shouldn't wait on a deno runtime.
+pub struct DigestCx<'a> {
+    pub cli_config: &'a crate::config::Config,
+    pub env_vars: &'a IndexMap<String, String>,
+    pub cwd: &'a Path,
+    pub file_hash_memo: &'a DHashMap<PathBuf, SharedFileContentDigestFuture>,
+    pub digest_memo: &'a DigestMemo,
+}
+
+impl HostCtx {
+    pub fn digest_cx(&self) -> DigestCx<'_> {
+        DigestCx {
+            cli_config: &self.gcx.config,
+            env_vars: &self.config.env_vars,
+            cwd: &self.config.cwd,
+            file_hash_memo: &self.file_hash_memo,
+            digest_memo: &self.digest_memo,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HashObj {
     pub version: String,
@@ -16,6 +40,16 @@ pub struct HashObj {
     /// File paths that were observed from the fs but not necessarily
     /// read.
     pub listed_files: Vec<PathBuf>,
+    /// Digests of the paths matching the glob patterns
+    /// that were expanded.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub glob_digests: IndexMap<PathBuf, String>,
+    /// The serialized config these digests were collected for.
+    ///
+    /// Absent on hashfiles migrated from versions that kept
+    /// the config in the lockfile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<Arc<super::SerializedConfig>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -33,8 +67,9 @@ impl HashObj {
         ghjkfile_path: &Path,
         res: &super::SerializationResult,
     ) -> Res<Self> {
+        let dcx = hcx.digest_cx();
         Ok(HashObj {
-            version: "0".into(),
+            version: super::migrate::HASHFILE_MIGRATIONS.current.into(),
             env_var_hashes: env_var_digests(
                 &hcx.config.env_vars,
                 res.accessed_env_keys.iter().map(|key| key.as_ref()),
@@ -50,8 +85,21 @@ impl HashObj {
                     .unwrap_or_log()
                 })
                 .collect(),
+            glob_digests: res
+                .glob_patterns
+                .iter()
+                .map(|pattern| {
+                    let pattern = pathdiff::diff_paths(
+                        std::path::absolute(pattern).expect_or_log("error absolutizing path"),
+                        &hcx.config.cwd,
+                    )
+                    .unwrap_or_log();
+                    let digest = glob_digest(&pattern)?;
+                    Ok((pattern, digest))
+                })
+                .collect::<Res<_>>()?,
             read_file_hashes: file_digests(
-                hcx,
+                &dcx,
                 res.read_file_paths
                     .iter()
                     .map(|path| path.as_ref())
@@ -59,6 +107,7 @@ impl HashObj {
             )
             .await?,
             cli_config: hcx.gcx.config.clone(),
+            config: None,
             deno_config: if let Some(path) = hcx.gcx.config.deno_json.as_ref() {
                 let raw = read_file(path)
                     .await
@@ -86,19 +135,59 @@ impl HashObj {
             Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
             Err(err) => return Err(HashfileError::Other(ferr!("error reading hashfile: {err}"))),
         };
-        serde_json::from_slice(&raw).map_err(HashfileError::Serialization)
+        let mut raw: serde_json::Value =
+            serde_json::from_slice(&raw).map_err(HashfileError::Serialization)?;
+        super::migrate::HASHFILE_MIGRATIONS
+            .migrate(&mut raw)
+            .map_err(HashfileError::Other)?;
+        serde_json::from_value(raw).map_err(HashfileError::Serialization)
+    }
+
+    /// Digest of the whole object, used to tag artifacts derived
+    /// from the serialization it describes.
+    pub fn digest(&self) -> String {
+        crate::utils::hash_obj(self)
     }
 
-    #[tracing::instrument(skip(hcx))]
-    pub async fn is_stale(&self, hcx: &HostCtx) -> Res<bool> {
+    pub async fn is_stale(&self, dcx: &DigestCx<'_>) -> Res<bool> {
+        Ok(self.staleness(dcx).await?.is_some())
+    }
+
+    /// Why the serialization described by this hashfile is out of date,
+    /// `None` if it's still fresh.
+    #[tracing::instrument(skip(dcx))]
+    pub async fn staleness(&self, dcx: &DigestCx<'_>) -> Res<Option<Staleness>> {
         {
-            if self.cli_config != hcx.gcx.config {
-                trace!("stale cli config");
-                return Ok(true);
+            // settings that have no bearing on the result of a
+            // serialization are taken from the recorded config
+            let cli_config = crate::config::Config {
+                serialization_timeout: self.cli_config.serialization_timeout,
+                auto_sync: self.cli_config.auto_sync,
+                auto_sync_timeout: self.cli_config.auto_sync_timeout,
+                // lock entries are scrubbed anew on every write
+                secret_env_vars: self.cli_config.secret_env_vars.clone(),
+                ..dcx.cli_config.clone()
+            };
+            if self.cli_config != cli_config {
+                let (old, new) = (
+                    serde_json::to_value(&self.cli_config)?,
+                    serde_json::to_value(&cli_config)?,
+                );
+                let fields = match (old, new) {
+                    (serde_json::Value::Object(old), serde_json::Value::Object(new)) => new
+                        .keys()
+                        .chain(old.keys())
+                        .unique()
+                        .filter(|key| old.get(*key) != new.get(*key))
+                        .cloned()
+                        .collect(),
+                    _ => vec![],
+                };
+                return Ok(Some(Staleness::CliConfig { fields }));
             }
         }
         {
-            if let Some(path) = hcx.gcx.config.deno_json.as_ref() {
+            if let Some(path) = dcx.cli_config.deno_json.as_ref() {
                 let raw = read_file(path)
                     .await
                     .map_err(|err| ferr!("error reading deno.json at {path:?}: {err}"))?
@@ -109,48 +198,136 @@ impl HashObj {
                     .map_err(|err| ferr!("error parsing deno.json at {path:?}: {err}"))?
                     .ok_or_else(|| ferr!("error parsing deno.json at {path:?}: empty??"))?;
                 if self.deno_config != Some(val) {
-                    trace!("stale deno.json");
-                    return Ok(true);
+                    return Ok(Some(Staleness::DenoConfig { path: path.clone() }));
                 }
             }
         }
         {
             let new_digest = env_var_digests(
-                &hcx.config.env_vars,
+                dcx.env_vars,
                 self.env_var_hashes.keys().map(|key| &key[..]),
             );
-            if self.env_var_hashes != new_digest {
-                trace!("stale env var digests");
-                return Ok(true);
+            let keys = self
+                .env_var_hashes
+                .iter()
+                .filter(|(key, hash)| new_digest.get(*key) != Some(*hash))
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            if !keys.is_empty() {
+                return Ok(Some(Staleness::EnvVars { keys }));
             }
         }
         {
+            let mut paths = vec![];
             for path in &self.listed_files {
                 if !crate::utils::file_exists(path).await? {
-                    trace!("stale listed files");
-                    return Ok(true);
+                    paths.push(path.clone());
                 }
             }
+            if !paths.is_empty() {
+                return Ok(Some(Staleness::ListedFiles { paths }));
+            }
         }
         {
-            if self.read_file_hashes
-                != file_digests(
-                    hcx,
-                    self.read_file_hashes
-                        .keys()
-                        .map(|path| path.as_ref())
-                        .collect(),
-                )
-                .await?
-            {
-                trace!("stale read files digest");
-                return Ok(true);
+            let mut patterns = vec![];
+            for (pattern, digest) in &self.glob_digests {
+                if glob_digest(pattern)? != *digest {
+                    patterns.push(pattern.clone());
+                }
+            }
+            if !patterns.is_empty() {
+                return Ok(Some(Staleness::GlobMatches { patterns }));
+            }
+        }
+        {
+            let new_digest = file_digests(
+                dcx,
+                self.read_file_hashes
+                    .keys()
+                    .map(|path| path.as_ref())
+                    .collect(),
+            )
+            .await?;
+            let paths = self
+                .read_file_hashes
+                .iter()
+                .filter(|(path, hash)| new_digest.get(*path) != Some(*hash))
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>();
+            if !paths.is_empty() {
+                return Ok(Some(Staleness::ReadFiles { paths }));
             }
         }
-        Ok(false)
+        Ok(None)
     }
 }
 
+/// The reason a hashfile doesn't describe the current state
+/// of the ghjkfile, requiring a re-serialization.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase", tag = "reason")]
+pub enum Staleness {
+    /// No hashfile was found.
+    Missing,
+    /// The hashfile couldn't be parsed.
+    Corrupt,
+    /// The hashfile was migrated from a version that kept
+    /// the serialized config in the lockfile.
+    NoConfig,
+    /// The ghjk config changed in the named fields.
+    CliConfig { fields: Vec<String> },
+    /// The deno.json file changed.
+    DenoConfig { path: PathBuf },
+    /// Env vars read by the ghjkfile changed.
+    EnvVars { keys: Vec<String> },
+    /// Files listed by the ghjkfile were removed.
+    ListedFiles { paths: Vec<PathBuf> },
+    /// The paths matching the expanded glob patterns changed.
+    GlobMatches { patterns: Vec<PathBuf> },
+    /// Files read by the ghjkfile changed.
+    ReadFiles { paths: Vec<PathBuf> },
+}
+
+impl std::fmt::Display for Staleness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn paths(paths: &[PathBuf]) -> String {
+            paths.iter().map(|path| path.display()).join(", ")
+        }
+        match self {
+            Staleness::Missing => write!(f, "no hashfile found"),
+            Staleness::Corrupt => write!(f, "hashfile is corrupt"),
+            Staleness::NoConfig => write!(f, "hashfile has no serialized config"),
+            Staleness::CliConfig { fields } => {
+                write!(f, "ghjk config changed: {}", fields.join(", "))
+            }
+            Staleness::DenoConfig { path } => {
+                write!(f, "deno.json changed: {}", path.display())
+            }
+            Staleness::EnvVars { keys } => write!(f, "env vars changed: {}", keys.join(", ")),
+            Staleness::ListedFiles { paths: list } => {
+                write!(f, "listed files removed: {}", paths(list))
+            }
+            Staleness::GlobMatches { patterns } => {
+                write!(f, "glob matches changed: {}", paths(patterns))
+            }
+            Staleness::ReadFiles { paths: list } => {
+                write!(f, "read files changed: {}", paths(list))
+            }
+        }
+    }
+}
+
+/// Digest of the paths matching the glob pattern.
+fn glob_digest(pattern: &Path) -> Res<String> {
+    let pattern = pattern.to_string_lossy();
+    let matches = glob::glob(&pattern)
+        .wrap_err_with(|| format!("invalid glob pattern {pattern:?}"))?
+        .filter_map(|res| res.ok())
+        .sorted()
+        .collect::<Vec<_>>();
+    Ok(crate::utils::hash_obj(&matches))
+}
+
 async fn read_file(path: &Path) -> Res<Option<Vec<u8>>> {
     let path = std::path::absolute(path)?;
     let path = match tokio::fs::canonicalize(path).await {
@@ -182,15 +359,15 @@ fn env_var_digests<'a>(
 }
 
 async fn file_digests(
-    hcx: &HostCtx,
+    dcx: &DigestCx<'_>,
     read_files: Vec<&Path>,
 ) -> Res<IndexMap<PathBuf, Option<String>>> {
     use futures::StreamExt;
     let mut map = futures::stream::iter(read_files.into_iter().map(|path| {
         async move {
             let path = std::path::absolute(path)?;
-            let hash = file_digest_hash(hcx, &path).await?;
-            let relative_path = pathdiff::diff_paths(path, &hcx.config.cwd).unwrap();
+            let hash = file_digest_hash(dcx, &path).await?;
+            let relative_path = pathdiff::diff_paths(path, dcx.cwd).unwrap();
             Ok((relative_path, hash))
         }
         .boxed()
@@ -204,8 +381,8 @@ async fn file_digests(
     Ok(map)
 }
 
-#[tracing::instrument(skip(hcx))]
-pub async fn file_digest_hash(hcx: &HostCtx, path: &Path) -> Res<Option<String>> {
+#[tracing::instrument(skip(dcx))]
+pub async fn file_digest_hash(dcx: &DigestCx<'_>, path: &Path) -> Res<Option<String>> {
     let path = match tokio::fs::canonicalize(path).await {
         Ok(val) => val,
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
@@ -224,22 +401,35 @@ pub async fn file_digest_hash(hcx: &HostCtx, path: &Path) -> Res<Option<String>>
                         "large file detected, skippin content hash"
                     );
                     None
+                } else if let Some(hash) = dcx.digest_memo.get(&path, &stat) {
+                    Some(hash)
                 } else {
-                    Some(
-                        file_content_digest_hash(hcx, &path)
-                            .await?
-                            .await
-                            .map_err(|err| ferr!(err))?,
-                    )
+                    let hash = file_content_digest_hash(dcx, &path)
+                        .await?
+                        .await
+                        .map_err(|err| ferr!(err))?;
+                    dcx.digest_memo.insert(path.clone(), &stat, hash.clone());
+                    Some(hash)
                 }
             } else {
                 None
             };
 
-            let stat = StatMeta {
-                // we're not going to invalidate on access
-                accessed: None,
-                ..StatMeta::from(stat)
+            let stat = if content_hash.is_some() {
+                // the contents are all that matter for files, this
+                // avoids invalidation on a mere touch
+                StatMeta {
+                    accessed: None,
+                    created: None,
+                    modified: None,
+                    ..StatMeta::from(stat)
+                }
+            } else {
+                StatMeta {
+                    // we're not going to invalidate on access
+                    accessed: None,
+                    ..StatMeta::from(stat)
+                }
             };
             let json = json!({
                 "content_hash": content_hash,
@@ -256,12 +446,12 @@ pub type SharedFileContentDigestFuture =
     futures::future::Shared<BoxFuture<'static, Result<CHeapStr, String>>>;
 
 async fn file_content_digest_hash(
-    hcx: &HostCtx,
+    dcx: &DigestCx<'_>,
     path: &Path,
 ) -> Res<SharedFileContentDigestFuture> {
     let path = path.to_owned();
     use dashmap::mapref::entry::*;
-    match hcx.file_hash_memo.entry(path.clone()) {
+    match dcx.file_hash_memo.entry(path.clone()) {
         Entry::Occupied(occupied_entry) => Ok(occupied_entry.get().clone()),
         Entry::Vacant(vacant_entry) => {
             let shared = vacant_entry
@@ -286,6 +476,156 @@ async fn file_content_digest_hash(
     }
 }
 
+/// Content digests of files keyed by their stat metadata.
+///
+/// This is persisted in the ghjkdir between invocations so that
+/// staleness checks only need to read the files whose size, mtime
+/// or inode changed since they were last hashed.
+#[derive(Debug, Default)]
+pub struct DigestMemo {
+    path: Option<PathBuf>,
+    entries: DHashMap<PathBuf, DigestMemoEntry>,
+    /// Paths looked up during this invocation, the rest
+    /// are dropped when persisting.
+    touched: dashmap::DashSet<PathBuf>,
+    dirty: std::sync::atomic::AtomicBool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DigestMemoFile {
+    version: String,
+    entries: DHashMap<PathBuf, DigestMemoEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DigestMemoEntry {
+    stat: StatKey,
+    content_hash: CHeapStr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StatKey {
+    size: u64,
+    /// Nanoseconds since the epoch.
+    modified: Option<u64>,
+    inode: Option<u64>,
+}
+
+impl StatKey {
+    fn new(stat: &std::fs::Metadata) -> Self {
+        Self {
+            size: stat.len(),
+            modified: stat
+                .modified()
+                .ok()
+                .and_then(|ts| ts.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
+                .map(|dur| dur.as_nanos() as u64),
+            #[cfg(unix)]
+            inode: Some(std::os::unix::fs::MetadataExt::ino(stat)),
+            #[cfg(not(unix))]
+            inode: None,
+        }
+    }
+}
+
+impl DigestMemo {
+    const FILE_NAME: &str = "digests.json";
+    const VERSION: &str = "0";
+
+    /// Load the memo persisted in the ghjkdir.
+    ///
+    /// Being a cache, missing or unreadable memos are treated as empty.
+    pub async fn load(ghjkdir_path: &Path) -> Self {
+        let path = ghjkdir_path.join(Self::FILE_NAME);
+        let entries = match tokio::fs::read(&path).await {
+            Ok(raw) => match serde_json::from_slice::<DigestMemoFile>(&raw) {
+                Ok(file) if file.version == Self::VERSION => file.entries,
+                Ok(file) => {
+                    debug!(
+                        version = file.version,
+                        "unsupported digest memo, discarding"
+                    );
+                    default()
+                }
+                Err(err) => {
+                    debug!(?err, "corrupt digest memo, discarding");
+                    default()
+                }
+            },
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    debug!(?err, "error reading digest memo, discarding");
+                }
+                default()
+            }
+        };
+        Self {
+            path: Some(path),
+            entries,
+            touched: default(),
+            dirty: default(),
+        }
+    }
+
+    /// Write out the memo if any new digests were added since it was loaded.
+    pub async fn persist(&self) -> Res<()> {
+        use std::sync::atomic::Ordering;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        // entries for files that weren't looked up have either
+        // been removed or are no longer part of the ghjkfile
+        self.entries
+            .retain(|file_path, _| self.touched.contains(file_path));
+        let raw = serde_json::to_vec(&json!({
+            "version": Self::VERSION,
+            "entries": &self.entries,
+        }))
+        .expect_or_log("error jsonifying digest memo");
+        // write to a temporary file first so that concurrent
+        // invocations never observe a partial file
+        let tmp_path = path.with_extension(format!("json.{}", std::process::id()));
+        tokio::fs::write(&tmp_path, raw)
+            .await
+            .wrap_err("error writing digest memo")?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .wrap_err("error writing digest memo")?;
+        Ok(())
+    }
+
+    fn get(&self, path: &Path, stat: &std::fs::Metadata) -> Option<CHeapStr> {
+        self.touched.insert(path.to_owned());
+        let entry = self.entries.get(path)?;
+        (entry.stat == StatKey::new(stat)).then(|| entry.content_hash.clone())
+    }
+
+    fn insert(&self, path: PathBuf, stat: &std::fs::Metadata, content_hash: CHeapStr) {
+        self.touched.insert(path.clone());
+        let stat = StatKey::new(stat);
+        // files modified in the last couple of seconds might be modified
+        // again without a change in mtime on filesystems with coarse
+        // timestamps so we avoid memoizing them
+        let recent = stat.modified.is_none_or(|modified| {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .map(|dur| dur.as_nanos() as u64)
+                .unwrap_or_default();
+            now.saturating_sub(modified) < 2_000_000_000
+        });
+        if recent {
+            self.entries.remove(&path);
+            return;
+        }
+        self.entries
+            .insert(path, DigestMemoEntry { stat, content_hash });
+        self.dirty.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
 #[derive(Serialize)]
 struct StatMeta {
     accessed: Option<u64>,
@@ -0,0 +1,254 @@
+//! Upgrading of lockfiles and hashfiles written by older versions of ghjk.
+//!
+//! Each format has a registry of steps that take the raw json from one
+//! version to the next. Documents are upgraded a step at a time until
+//! they reach the current version so that a step only ever needs to
+//! know about the version right before it.
+
+use crate::interlude::*;
+
+/// Upgrades a document from the `from` version to the `to` version.
+pub struct MigrationStep {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub apply: fn(serde_json::Value) -> Res<serde_json::Value>,
+}
+
+pub struct Migrations {
+    /// Name of the migrated document used in messages.
+    pub name: &'static str,
+    pub current: &'static str,
+    pub steps: &'static [MigrationStep],
+}
+
+impl Migrations {
+    /// Upgrade the document in place to the current version.
+    ///
+    /// Returns whether any steps were applied.
+    pub fn migrate(&self, raw: &mut serde_json::Value) -> Res<bool> {
+        let mut applied = 0;
+        loop {
+            let version = raw
+                .get("version")
+                .and_then(|val| val.as_str())
+                .ok_or_else(|| ferr!("{} has no version tag", self.name))?;
+            if version == self.current {
+                return Ok(applied > 0);
+            }
+            let Some(step) = self.steps.iter().find(|step| step.from == version) else {
+                eyre::bail!(
+                    "unsupported {} version {version:?}, expected {:?} or older",
+                    self.name,
+                    self.current
+                );
+            };
+            // guard against registries that loop back on themselves
+            if applied == self.steps.len() {
+                eyre::bail!("cyclic migrations found for {}", self.name);
+            }
+            debug!(
+                name = self.name,
+                from = step.from,
+                to = step.to,
+                "migrating"
+            );
+            *raw = (step.apply)(std::mem::take(raw)).wrap_err_with(|| {
+                format!(
+                    "error migrating {} from version {:?} to {:?}",
+                    self.name, step.from, step.to
+                )
+            })?;
+            let Some(obj) = raw.as_object_mut() else {
+                eyre::bail!(
+                    "{} migration to {:?} produced a non object",
+                    self.name,
+                    step.to
+                );
+            };
+            obj.insert("version".into(), step.to.into());
+            applied += 1;
+        }
+    }
+
+    /// Owned version of [`Self::migrate`].
+    pub fn migrated(&self, mut raw: serde_json::Value) -> Res<serde_json::Value> {
+        self.migrate(&mut raw)?;
+        Ok(raw)
+    }
+}
+
+pub static LOCKFILE_MIGRATIONS: Migrations = Migrations {
+    name: "lockfile",
+    current: "1",
+    steps: &[MigrationStep {
+        from: "0",
+        to: "1",
+        // the serialized config moved to the hashfile
+        apply: |mut raw| {
+            if let Some(obj) = raw.as_object_mut() {
+                obj.remove("config");
+            }
+            Ok(raw)
+        },
+    }],
+};
+
+pub static HASHFILE_MIGRATIONS: Migrations = Migrations {
+    name: "hashfile",
+    current: "1",
+    steps: &[MigrationStep {
+        from: "0",
+        to: "1",
+        // the config that moved here from the lockfile can't be recovered
+        // and is left out, forcing a re-serialization
+        apply: Ok,
+    }],
+};
+
+/// Migrations of the lock entries of the systems implemented in rust.
+///
+/// Systems implemented in typescript migrate their entries as
+/// part of loading them.
+pub fn sys_lock_entry_migrations(sys_id: &str) -> Option<&'static Migrations> {
+    match sys_id {
+        "envs" => Some(&crate::systems::envs::LOCK_ENTRY_MIGRATIONS),
+        "tasks" => Some(&crate::systems::tasks::LOCK_ENTRY_MIGRATIONS),
+        _ => None,
+    }
+}
+
+/// Upgrade the lockfile along with the lock entries of the rust systems.
+///
+/// Returns whether anything was changed.
+pub fn migrate_lockfile(raw: &mut serde_json::Value) -> Res<bool> {
+    let mut changed = LOCKFILE_MIGRATIONS.migrate(raw)?;
+    if let Some(entries) = raw
+        .get_mut("sys_entries")
+        .and_then(|entries| entries.as_object_mut())
+    {
+        for (sys_id, entry) in entries {
+            if let Some(migrations) = sys_lock_entry_migrations(sys_id) {
+                changed |= migrations
+                    .migrate(entry)
+                    .wrap_err_with(|| format!("error migrating lock entry of system {sys_id:?}"))?;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static CHAIN: Migrations = Migrations {
+        name: "doc",
+        current: "2",
+        steps: &[
+            // registry order shouldn't matter
+            MigrationStep {
+                from: "1",
+                to: "2",
+                apply: |mut raw| {
+                    raw["c"] = true.into();
+                    // steps don't have to tag the version themselves
+                    raw["version"] = "bogus".into();
+                    Ok(raw)
+                },
+            },
+            MigrationStep {
+                from: "0",
+                to: "1",
+                apply: |mut raw| {
+                    let obj = raw.as_object_mut().unwrap();
+                    let val = obj.remove("a").unwrap();
+                    obj.insert("b".into(), val);
+                    Ok(raw)
+                },
+            },
+        ],
+    };
+
+    #[test]
+    fn migrates_through_the_chain() -> Res<()> {
+        let mut raw = json!({ "version": "0", "a": 1 });
+        assert!(CHAIN.migrate(&mut raw)?);
+        assert_eq!(raw, json!({ "version": "2", "b": 1, "c": true }));
+
+        let mut raw = json!({ "version": "1", "b": 1 });
+        assert!(CHAIN.migrate(&mut raw)?);
+        assert_eq!(raw, json!({ "version": "2", "b": 1, "c": true }));
+
+        // current documents are left as is
+        let mut raw = json!({ "version": "2", "b": 1 });
+        assert!(!CHAIN.migrate(&mut raw)?);
+        assert_eq!(raw, json!({ "version": "2", "b": 1 }));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let err = CHAIN
+            .migrate(&mut json!({ "version": "3" }))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unsupported doc version"), "{err}");
+        let err = CHAIN.migrate(&mut json!({})).unwrap_err().to_string();
+        assert!(err.contains("has no version tag"), "{err}");
+    }
+
+    #[test]
+    fn rejects_non_object_results() {
+        static BAD: Migrations = Migrations {
+            name: "doc",
+            current: "1",
+            steps: &[MigrationStep {
+                from: "0",
+                to: "1",
+                apply: |_| Ok(json!([])),
+            }],
+        };
+        let err = BAD
+            .migrate(&mut json!({ "version": "0" }))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("produced a non object"), "{err}");
+    }
+
+    #[test]
+    fn guards_against_cycles() {
+        static CYCLIC: Migrations = Migrations {
+            name: "doc",
+            current: "2",
+            steps: &[
+                MigrationStep {
+                    from: "0",
+                    to: "1",
+                    apply: Ok,
+                },
+                MigrationStep {
+                    from: "1",
+                    to: "0",
+                    apply: Ok,
+                },
+            ],
+        };
+        let err = CYCLIC
+            .migrate(&mut json!({ "version": "0" }))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cyclic migrations"), "{err}");
+    }
+
+    #[test]
+    fn lockfile_v0_drops_the_config() -> Res<()> {
+        let mut raw = json!({
+            "version": "0",
+            "config": { "modules": [] },
+            "sys_entries": {},
+        });
+        assert!(migrate_lockfile(&mut raw)?);
+        assert_eq!(raw, json!({ "version": "1", "sys_entries": {} }));
+        Ok(())
+    }
+}
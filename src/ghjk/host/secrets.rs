@@ -0,0 +1,165 @@
+//! Keeping the values of secret env vars out of the lockfile.
+//!
+//! Systems are free to capture env vars like `GITHUB_TOKEN` in their
+//! lock entries. Since the lockfile is intended to be checked in,
+//! any such values are replaced with references before it's written
+//! and the references are replaced back when the entries are loaded.
+
+use crate::interlude::*;
+
+/// Patterns of env var names that are always treated as secret.
+pub const DEFAULT_SECRET_ENV_VARS: &[&str] = &["*_TOKEN", "*_SECRET", "*_PASSWORD", "*_API_KEY"];
+
+/// Values shorter than this are too likely to occur by chance
+/// to be scrubbed.
+const MIN_SECRET_LEN: usize = 8;
+
+#[derive(Debug, Default)]
+pub struct Secrets {
+    /// Pairs of env var names and values, longest values first
+    /// so that values containing others are replaced first.
+    vars: Vec<(String, String)>,
+    /// All the env vars, references are restored from these so that
+    /// entries scrubbed under other patterns still load.
+    env_vars: IndexMap<String, String>,
+}
+
+impl Secrets {
+    pub fn new(env_vars: &IndexMap<String, String>, patterns: &[String]) -> Self {
+        let mut vars = env_vars
+            .iter()
+            .filter(|(key, val)| {
+                val.len() >= MIN_SECRET_LEN
+                    && DEFAULT_SECRET_ENV_VARS
+                        .iter()
+                        .copied()
+                        .chain(patterns.iter().map(|pat| &pat[..]))
+                        .any(|pat| matches_pattern(pat, key))
+            })
+            .map(|(key, val)| (key.clone(), val.clone()))
+            .collect::<Vec<_>>();
+        vars.sort_by(|(_, a), (_, b)| b.len().cmp(&a.len()));
+        Self {
+            vars,
+            env_vars: env_vars.clone(),
+        }
+    }
+
+    fn reference(key: &str) -> String {
+        format!("${{secret:{key}}}")
+    }
+
+    /// Names of the env vars whose values occur in the json.
+    pub fn find(&self, val: &serde_json::Value) -> Vec<&str> {
+        let mut found = vec![];
+        visit_strings(val, &mut |string| {
+            for (key, secret) in &self.vars {
+                if string.contains(&secret[..]) && !found.contains(&&key[..]) {
+                    found.push(&key[..]);
+                }
+            }
+        });
+        found
+    }
+
+    /// Replace the values of secret env vars in the json with references.
+    ///
+    /// Returns the names of the env vars that were found.
+    pub fn scrub(&self, val: &mut serde_json::Value) -> Vec<&str> {
+        let found = self.find(val);
+        if !found.is_empty() {
+            visit_strings_mut(val, &mut |string| {
+                for (key, secret) in &self.vars {
+                    if string.contains(&secret[..]) {
+                        *string = string.replace(&secret[..], &Self::reference(key));
+                    }
+                }
+            });
+        }
+        found
+    }
+
+    /// Replace references in the json with the values of the env vars.
+    ///
+    /// References to env vars that aren't set are left as is.
+    pub fn restore(&self, val: &mut serde_json::Value) {
+        const PREFIX: &str = "${secret:";
+        let mut unresolved = false;
+        visit_strings_mut(val, &mut |string| {
+            if !string.contains(PREFIX) {
+                return;
+            }
+            let mut out = String::with_capacity(string.len());
+            let mut rest = &string[..];
+            while let Some(start) = rest.find(PREFIX) {
+                out.push_str(&rest[..start]);
+                rest = &rest[start..];
+                let secret = rest[PREFIX.len()..]
+                    .find('}')
+                    .map(|end| &rest[PREFIX.len()..PREFIX.len() + end])
+                    .and_then(|key| Some((key, self.env_vars.get(key)?)));
+                match secret {
+                    Some((key, secret)) => {
+                        out.push_str(secret);
+                        rest = &rest[Self::reference(key).len()..];
+                    }
+                    None => {
+                        unresolved = true;
+                        out.push_str(PREFIX);
+                        rest = &rest[PREFIX.len()..];
+                    }
+                }
+            }
+            out.push_str(rest);
+            *string = out;
+        });
+        if unresolved {
+            warn!("lockfile references secret env vars that are not set");
+        }
+    }
+}
+
+/// Glob style matching where `*` matches any run of characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // no wildcards
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn visit_strings(val: &serde_json::Value, visitor: &mut impl FnMut(&str)) {
+    use serde_json::Value;
+    match val {
+        Value::String(string) => visitor(string),
+        Value::Array(arr) => arr.iter().for_each(|val| visit_strings(val, visitor)),
+        Value::Object(obj) => obj.values().for_each(|val| visit_strings(val, visitor)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+fn visit_strings_mut(val: &mut serde_json::Value, visitor: &mut impl FnMut(&mut String)) {
+    use serde_json::Value;
+    match val {
+        Value::String(string) => visitor(string),
+        Value::Array(arr) => arr
+            .iter_mut()
+            .for_each(|val| visit_strings_mut(val, visitor)),
+        Value::Object(obj) => obj
+            .values_mut()
+            .for_each(|val| visit_strings_mut(val, visitor)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
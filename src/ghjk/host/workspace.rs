@@ -0,0 +1,374 @@
+//! Workspaces, ghjkfiles that pull in the configs of member ghjkfiles.
+//!
+//! Each member is a directory with its own ghjkfile. Members are
+//! serialized alongside the workspace's ghjkfile and their configs
+//! are merged into its config, namespaced under the name of their
+//! directory: tasks as `member:task` and envs as `member/env`.
+//! The systems then see a single config, sharing the lockfile and
+//! the port installs between all the members.
+//!
+//! The keys of member envs are used for their dirs and are rehashed
+//! instead of namespaced to keep them flat and path safe.
+
+use crate::interlude::*;
+
+use crate::systems::envs::types::{
+    EnvRecipe, EnvsModuleConfig, PosixExecHook, Provision, WellKnownProvision,
+};
+use crate::systems::tasks::types::{TaskDefHashed, TasksModuleConfig};
+use crate::systems::SystemConfig;
+
+use super::{SerializationResult, SerializedConfig};
+
+const MEMBER_GHJKFILE_NAMES: [&str; 3] = ["ghjk.ts", "ghjk.toml", "ghjk.json"];
+
+/// Serialize the members declared by the workspace's ghjkfile
+/// and merge them into its serialization result.
+pub async fn add_members(
+    hcx: &super::HostCtx,
+    ghjkfile_path: &Path,
+    res: &mut SerializationResult,
+) -> Res<()> {
+    let root_dir = ghjkfile_path
+        .parent()
+        .expect_or_log("ghjkfile has no parent");
+    let mut names = vec![];
+    for member_dir in resolve_member_dirs(root_dir, &res.config.members, &mut res.glob_patterns)? {
+        let name = member_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| ferr!("invalid workspace member dir {member_dir:?}"))?
+            .to_string();
+        if name.contains([':', '/']) {
+            eyre::bail!("workspace member name '{name}' can't contain ':' or '/'");
+        }
+        // the dir of the member's named envs would shadow the default env's link
+        if name == "default" {
+            eyre::bail!("workspace member can't be named 'default'");
+        }
+        if names.contains(&name) {
+            eyre::bail!("more than one workspace member named '{name}'");
+        }
+
+        let mut member_ghjkfile = None;
+        for file_name in MEMBER_GHJKFILE_NAMES {
+            let path = member_dir.join(file_name);
+            if crate::utils::file_exists(&path).await? {
+                member_ghjkfile = Some(path);
+                break;
+            }
+        }
+        let Some(member_ghjkfile) = member_ghjkfile else {
+            eyre::bail!("no ghjkfile found for workspace member at {member_dir:?}");
+        };
+
+        debug!(?member_ghjkfile, "serializing workspace member");
+        let member_res = super::serialize_single_ghjkfile(hcx, &member_ghjkfile)
+            .await
+            .wrap_err_with(|| format!("error serializing workspace member '{name}'"))?;
+        if !member_res.config.members.is_empty() {
+            eyre::bail!(
+                "workspace member '{name}' declares members, nested workspaces are not supported"
+            );
+        }
+
+        let ns = Namespace {
+            dir: pathdiff::diff_paths(&member_dir, root_dir).unwrap_or_log(),
+            ghjkfile: pathdiff::diff_paths(&member_ghjkfile, root_dir).unwrap_or_log(),
+            name: name.clone(),
+        };
+        merge_config(&mut res.config, member_res.config, &ns)
+            .wrap_err_with(|| format!("error merging config of workspace member '{name}'"))?;
+
+        for key in member_res.accessed_env_keys {
+            if !res.accessed_env_keys.contains(&key) {
+                res.accessed_env_keys.push(key);
+            }
+        }
+        res.read_file_paths.extend(member_res.read_file_paths);
+        res.listed_file_paths.extend(member_res.listed_file_paths);
+        res.glob_patterns.extend(member_res.glob_patterns);
+        names.push(name);
+    }
+    // members with no tasks still get listed
+    if let Some(module) = res
+        .config
+        .modules
+        .iter_mut()
+        .find(|module| module.id == "tasks")
+    {
+        let mut config: TasksModuleConfig = serde_json::from_value(module.config.take())?;
+        config.members = names;
+        module.config = serde_json::to_value(config)?;
+    }
+    Ok(())
+}
+
+/// Member entries are paths relative to the workspace's dir
+/// and may contain glob patterns.
+///
+/// The patterns are recorded so that the hashfile can
+/// track the members that show up later.
+fn resolve_member_dirs(
+    root_dir: &Path,
+    members: &[String],
+    glob_patterns: &mut Vec<PathBuf>,
+) -> Res<Vec<PathBuf>> {
+    let mut dirs = vec![];
+    for member in members {
+        let path = root_dir.join(member);
+        if !member.contains(['*', '?', '[']) {
+            dirs.push(path);
+            continue;
+        }
+        let pattern = path.to_string_lossy();
+        let matches = glob::glob(&pattern)
+            .wrap_err_with(|| format!("invalid workspace member pattern '{member}'"))?
+            .filter_map(|res| res.ok())
+            .filter(|path| path.is_dir())
+            .sorted();
+        dirs.extend(matches);
+        glob_patterns.push(path);
+    }
+    Ok(dirs.into_iter().unique().collect())
+}
+
+struct Namespace {
+    name: String,
+    /// The member's dir relative to the workspace's
+    dir: PathBuf,
+    /// The member's ghjkfile relative to the workspace's dir
+    ghjkfile: PathBuf,
+}
+
+impl Namespace {
+    fn task(&self, key: &str) -> String {
+        format!("{}:{key}", self.name)
+    }
+
+    /// Used for both env names and install set ids.
+    fn env(&self, key: &str) -> String {
+        format!("{}/{key}", self.name)
+    }
+}
+
+fn merge_config(
+    config: &mut SerializedConfig,
+    member: SerializedConfig,
+    ns: &Namespace,
+) -> Res<()> {
+    // blackboard entries are keyed by the hashes of their
+    // contents so they can be merged as is
+    Arc::make_mut(&mut config.blackboard).extend(Arc::unwrap_or_clone(member.blackboard));
+
+    for module in &member.modules {
+        if !config
+            .modules
+            .iter()
+            .any(|existing| existing.id == module.id)
+        {
+            config.modules.push(SystemConfig {
+                id: module.id.clone(),
+                config: serde_json::Value::Null,
+            });
+        }
+    }
+    // the envs go first as the tasks refer to their new keys
+    let mut env_keys = HashMap::new();
+    for module in member
+        .modules
+        .into_iter()
+        .sorted_by_key(|module| module.id != "envs")
+    {
+        let existing = config
+            .modules
+            .iter_mut()
+            .find(|existing| existing.id == module.id)
+            .expect_or_log("module not found");
+        match &module.id[..] {
+            "ports" => merge_ports(&mut existing.config, module.config, ns)?,
+            "tasks" => merge_tasks(&mut existing.config, module.config, ns, &env_keys)?,
+            "envs" => merge_envs(&mut existing.config, module.config, ns, &mut env_keys)?,
+            id => eyre::bail!("module '{id}' is not supported in workspace members"),
+        }
+    }
+    Ok(())
+}
+
+fn merge_ports(
+    config: &mut serde_json::Value,
+    member: serde_json::Value,
+    ns: &Namespace,
+) -> Res<()> {
+    let serde_json::Value::Object(mut member) = member else {
+        eyre::bail!("unexpected ports module config");
+    };
+    let Some(serde_json::Value::Object(sets)) = member.remove("sets") else {
+        eyre::bail!("unexpected ports module config");
+    };
+    if config.is_null() {
+        *config = json!({ "sets": {} });
+    }
+    let root_sets = config
+        .get_mut("sets")
+        .and_then(|sets| sets.as_object_mut())
+        .ok_or_else(|| ferr!("unexpected ports module config"))?;
+    for (id, set) in sets {
+        root_sets.insert(ns.env(&id), set);
+    }
+    Ok(())
+}
+
+fn merge_tasks(
+    config: &mut serde_json::Value,
+    member: serde_json::Value,
+    ns: &Namespace,
+    env_keys: &HashMap<String, String>,
+) -> Res<()> {
+    let member: TasksModuleConfig =
+        serde_json::from_value(member).wrap_err("error parsing tasks module config")?;
+    let mut root: TasksModuleConfig = if config.is_null() {
+        TasksModuleConfig {
+            tasks: default(),
+            tasks_named: vec![],
+            members: vec![],
+        }
+    } else {
+        serde_json::from_value(config.take()).wrap_err("error parsing tasks module config")?
+    };
+    for (key, mut def) in member.tasks {
+        let (depends_on, env_key, ghjkfile) = match &mut def {
+            TaskDefHashed::DenoFileV1(def) => {
+                (&mut def.depends_on, &mut def.env_key, &mut def.ghjkfile)
+            }
+            TaskDefHashed::CmdV1(def) => (&mut def.depends_on, &mut def.env_key, &mut def.ghjkfile),
+        };
+        for dep in depends_on.iter_mut().flatten() {
+            *dep = ns.task(dep);
+        }
+        let Some(new_key) = env_keys.get(&env_key[..]) else {
+            eyre::bail!("task '{key}' refers to unknown env '{env_key}'");
+        };
+        *env_key = new_key.clone();
+        *ghjkfile = Some(ns.ghjkfile.clone());
+        root.tasks.insert(ns.task(&key), def);
+    }
+    root.tasks_named
+        .extend(member.tasks_named.iter().map(|name| ns.task(name)));
+    *config = serde_json::to_value(root)?;
+    Ok(())
+}
+
+fn merge_envs(
+    config: &mut serde_json::Value,
+    member: serde_json::Value,
+    ns: &Namespace,
+    env_keys: &mut HashMap<String, String>,
+) -> Res<()> {
+    let member: EnvsModuleConfig =
+        serde_json::from_value(member).wrap_err("error parsing envs module config")?;
+    // the default env of the members are ignored and the
+    // workspace's own has to be there for them to fall back on
+    if config.is_null() {
+        eyre::bail!(
+            "workspace ghjkfile has no envs module to merge member '{}' into",
+            ns.name
+        );
+    }
+    let mut root: EnvsModuleConfig =
+        serde_json::from_value(config.take()).wrap_err("error parsing envs module config")?;
+    let mut recipes = vec![];
+    for (key, mut recipe) in member.envs {
+        recipe.provides = recipe
+            .provides
+            .into_iter()
+            .map(|prov| namespace_provision(prov, ns))
+            .collect();
+        // provenance is left out of the hash like in `ghjk.ts` files
+        let new_key = crate::utils::hash_obj(&EnvRecipe {
+            provenance: None,
+            ..recipe.clone()
+        });
+        env_keys.insert(key, new_key.clone());
+        recipes.push((new_key, recipe));
+    }
+    for (key, mut recipe) in recipes {
+        if let Some(provenance) = &mut recipe.provenance {
+            provenance.name = ns.env(&provenance.name);
+            for parent in &mut provenance.parents {
+                parent.name = ns.env(&parent.name);
+                if let Some(new_key) = env_keys.get(&parent.env_key) {
+                    parent.env_key = new_key.clone();
+                }
+            }
+            for source in &mut provenance.sources {
+                *source = ns.env(source);
+            }
+        }
+        root.envs.entry(key).or_insert(recipe);
+    }
+    for (name, key) in member.envs_named {
+        let Some(new_key) = env_keys.get(&key) else {
+            eyre::bail!("env '{name}' refers to unknown env key '{key}'");
+        };
+        root.envs_named.insert(ns.env(&name), new_key.clone());
+    }
+    *config = serde_json::to_value(root)?;
+    Ok(())
+}
+
+/// Update the references to tasks, install sets and files
+/// in the provision to point to the member's items.
+fn namespace_provision(prov: Provision, ns: &Namespace) -> Provision {
+    match prov {
+        Provision::WellKnown(WellKnownProvision::HookOnEnterPosixExec(hook)) => {
+            Provision::WellKnown(WellKnownProvision::HookOnEnterPosixExec(namespace_hook(
+                hook, ns,
+            )))
+        }
+        Provision::WellKnown(WellKnownProvision::HookOnExitPosixExec(hook)) => {
+            Provision::WellKnown(WellKnownProvision::HookOnExitPosixExec(namespace_hook(
+                hook, ns,
+            )))
+        }
+        Provision::WellKnown(prov) => Provision::WellKnown(prov),
+        Provision::Strange(mut val) => {
+            if let Some(obj) = val.as_object_mut() {
+                let ty = obj.get("ty").and_then(|ty| ty.as_str()).map(String::from);
+                if let Some("posix.envVarCmd" | "posix.envVarGit") = ty.as_deref() {
+                    // these run in the member's dir instead of the workspace's
+                    let working_dir = match obj.get("workingDir").and_then(|dir| dir.as_str()) {
+                        Some(dir) => ns.dir.join(dir),
+                        None => ns.dir.clone(),
+                    };
+                    obj.insert(
+                        "workingDir".into(),
+                        working_dir.to_string_lossy().into_owned().into(),
+                    );
+                    return Provision::Strange(val);
+                }
+                let (field, namespaced): (_, fn(&Namespace, &str) -> String) = match ty.as_deref() {
+                    Some("ghjk.ports.InstallSetRef") => ("setId", |ns, id| ns.env(id)),
+                    Some("posix.envVarDyn") => ("taskKey", |ns, key| ns.task(key)),
+                    Some("posix.envVarFile") => ("path", |ns, path| {
+                        // relative paths are resolved against the workspace's dir
+                        ns.dir.join(path).to_string_lossy().into_owned()
+                    }),
+                    _ => return Provision::Strange(val),
+                };
+                if let Some(serde_json::Value::String(item)) = obj.get_mut(field) {
+                    *item = namespaced(ns, item);
+                }
+            }
+            Provision::Strange(val)
+        }
+    }
+}
+
+/// Hooks that run tasks are serialized as `ghjk x <task>` invocations.
+fn namespace_hook(mut hook: PosixExecHook, ns: &Namespace) -> PosixExecHook {
+    if hook.program == "ghjk" && hook.arguments.len() == 2 && hook.arguments[0] == "x" {
+        hook.arguments[1] = ns.task(&hook.arguments[1]);
+    }
+    hook
+}
@@ -78,6 +78,9 @@ pub async fn systems_from_deno(
             import_map: _,
             deno_no_lockfile: _,
             completions: _,
+            shims: _,
+            auto_sync: _,
+            auto_sync_timeout: _,
         } = &gcx.config;
 
         json!(BindingArgs {
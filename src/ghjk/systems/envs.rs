@@ -6,7 +6,10 @@ use std::collections::HashMap;
 
 use crate::systems::{ConfigBlackboard, SystemCliCommand, SystemInstance};
 
+pub mod hooks;
 pub mod posix;
+pub mod reducers;
+pub mod template;
 pub mod types;
 use types::{EnvsModuleConfig, ProvisionReducerStore};
 
@@ -22,6 +25,16 @@ pub struct EnvsCtx {
     /// Store for provision reducers
     #[educe(Debug(ignore))]
     reducer_store: Arc<ProvisionReducerStore>,
+    /// Names offered by the generated shell completions, set
+    /// once the ghjkfile systems have loaded their configs.
+    completion_candidates: std::sync::OnceLock<CompletionCandidates>,
+}
+
+/// Dynamic values for the completion scripts baked into activators.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionCandidates {
+    pub env_names: Vec<String>,
+    pub task_names: Vec<String>,
 }
 
 type ReduceCallback =
@@ -37,6 +50,10 @@ impl EnvsCtx {
     pub fn register_reducer(&self, ty: String, reducer: types::ProvisionReducer) {
         self.reducer_store.insert(ty, reducer);
     }
+
+    pub fn completion_candidates(&self) -> Option<&CompletionCandidates> {
+        self.completion_candidates.get()
+    }
 }
 
 pub async fn system(
@@ -48,8 +65,17 @@ pub async fn system(
         ghjkdir_path: ghjkdir_path.to_path_buf(),
         reduce_callback: default(),
         reducer_store: default(),
+        completion_candidates: default(),
     });
 
+    let dyn_var_cache = Arc::new(reducers::DynEnvVarCache::default());
+    for ty in types::DynEnvVarSource::TYPES {
+        ecx.register_reducer(
+            ty.to_string(),
+            reducers::dyn_env_var_reducer(gcx.clone(), dyn_var_cache.clone()),
+        );
+    }
+
     Ok((EnvsSystemManifest { ecx: ecx.clone() }, ecx))
 }
 
@@ -66,6 +92,13 @@ impl EnvsSystemManifest {
     }
 }
 
+pub static LOCK_ENTRY_MIGRATIONS: crate::host::migrate::Migrations =
+    crate::host::migrate::Migrations {
+        name: "envs lock entry",
+        current: "0",
+        steps: &[],
+    };
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EnvsLockState {
     pub version: String,
@@ -129,15 +162,12 @@ impl SystemInstance for EnvsSystemInstance {
     }
 
     async fn load_lock_entry(&self, raw: serde_json::Value) -> Res<Self::LockState> {
-        let entry: EnvsLockState = serde_json::from_value(raw)?;
-        if entry.version != "0" {
-            eyre::bail!("unexpected version tag deserializing lockEntry");
-        }
+        let entry: EnvsLockState = serde_json::from_value(LOCK_ENTRY_MIGRATIONS.migrated(raw)?)?;
         Ok(entry)
     }
 
     async fn gen_lock_entry(&self) -> Res<serde_json::Value> {
-        Ok(serde_json::json!({ "version": "0" }))
+        Ok(serde_json::json!({ "version": LOCK_ENTRY_MIGRATIONS.current }))
     }
 
     async fn commands(&self) -> Res<Vec<SystemCliCommand>> {
@@ -159,6 +189,9 @@ impl SystemInstance for EnvsSystemInstance {
                         crate::systems::tasks::types::TaskDefHashed::DenoFileV1(def) => {
                             def.env_key.clone()
                         }
+                        crate::systems::tasks::types::TaskDefHashed::CmdV1(def) => {
+                            def.env_key.clone()
+                        }
                     })
                     .ok_or_else(|| ferr!("task with key '{task_name}' not found"))?;
 
@@ -184,6 +217,30 @@ impl SystemInstance for EnvsSystemInstance {
             }
         }
 
+        {
+            let state: Arc<LoadedState> = self.scx.get_bb(EnvsSystemInstance::BB_STATE_KEY);
+            let task_names = if self
+                .scx
+                .blackboard
+                .contains_key(crate::systems::tasks::TasksSystemInstance::BB_STATE_KEY)
+            {
+                let tasks_state: Arc<crate::systems::tasks::LoadedState> = self
+                    .scx
+                    .get_bb(crate::systems::tasks::TasksSystemInstance::BB_STATE_KEY);
+                tasks_state.config.tasks_named.clone()
+            } else {
+                vec![]
+            };
+            // commands are only collected once per invocation
+            self.ecx
+                .completion_candidates
+                .set(CompletionCandidates {
+                    env_names: state.config.envs_named.keys().cloned().collect(),
+                    task_names,
+                })
+                .ok();
+        }
+
         #[derive(clap::Parser)]
         #[clap(name = "envs")]
         #[clap(visible_alias = "e")]
@@ -204,20 +261,69 @@ impl SystemInstance for EnvsSystemInstance {
                     async move {
                         let state: Arc<LoadedState> = scx.get_bb(EnvsSystemInstance::BB_STATE_KEY);
                         match EnvsCommands::from_arg_matches(&matches) {
-                            Ok(EnvsCommands::Ls) => {
-                                list_envs(&state).await;
+                            Ok(EnvsCommands::Ls { json, all }) => {
+                                list_envs(&ecx, &state, json, all)
+                            }
+                            Ok(EnvsCommands::Show {
+                                env_key,
+                                task_env,
+                                explain,
+                                tree,
+                            }) => {
+                                let (env_key, env_name) =
+                                    env_key_args(&state, &scx, task_env, env_key)?;
+                                if tree {
+                                    show_env_tree(&state, env_key.as_str(), env_name.as_deref())
+                                } else if explain {
+                                    explain_env(
+                                        &ecx,
+                                        &state,
+                                        env_key.as_str(),
+                                        env_name.as_deref(),
+                                    )
+                                    .await
+                                } else {
+                                    show_env(&state, env_key.as_str(), env_name.as_deref())
+                                }
+                            }
+                            Ok(EnvsCommands::Status(StatusArgs { env, auto_sync })) => {
+                                let status = if auto_sync {
+                                    env_status_auto_sync(
+                                        &ecx.gcx.config,
+                                        &ecx.ghjkdir_path,
+                                        env.as_deref(),
+                                    )
+                                    .await?
+                                } else {
+                                    env_status(&ecx.gcx.config, &ecx.ghjkdir_path, env.as_deref())
+                                        .await?
+                                };
+                                println!("{status}");
                                 Ok(())
                             }
-                            Ok(EnvsCommands::Show { env_key, task_env }) => {
+                            Ok(EnvsCommands::Gc { dry_run }) => {
+                                gc_envs(&ecx, &state, dry_run).await
+                            }
+                            Ok(EnvsCommands::Which {
+                                bin,
+                                env_key,
+                                task_env,
+                            }) => {
                                 let (env_key, env_name) =
                                     env_key_args(&state, &scx, task_env, env_key)?;
-                                show_env(&state, env_key.as_str(), env_name.as_deref())
+                                which_bin(&ecx, &bin, env_key.as_str(), env_name.as_deref())
+                                    .await
                             }
                             Ok(EnvsCommands::Activate { env_key, task_env }) => {
                                 let (env_key, _) = env_key_args(&state, &scx, task_env, env_key)?;
                                 activate_env(env_key).await
                             }
-                            Ok(EnvsCommands::Cook { env_key, task_env }) => {
+                            Ok(EnvsCommands::Cook { all: true, .. }) => {
+                                cook_all_envs(&ecx, &scx, &state).await
+                            }
+                            Ok(EnvsCommands::Cook {
+                                env_key, task_env, ..
+                            }) => {
                                 let (env_key, env_name) =
                                     env_key_args(&state, &scx, task_env, env_key)?;
                                 reduce_and_cook(
@@ -292,7 +398,14 @@ struct LoadedState {
 #[derive(clap::Subcommand, Debug)]
 enum EnvsCommands {
     /// List environments defined in the ghjkfile
-    Ls,
+    Ls {
+        /// Print a JSON array with details about each env
+        #[arg(long)]
+        json: bool,
+        /// Include hidden envs, those with names starting with `_`
+        #[arg(long)]
+        all: bool,
+    },
     /// Cook the environment to a posix shell
     Cook {
         /// The environment to cook
@@ -303,6 +416,11 @@ enum EnvsCommands {
         /// Activate the environment used by the named task
         #[arg(short, long, value_name = "TASK NAME", conflicts_with = "env_key")]
         task_env: Option<String>,
+        /// Cook all the environments in the config
+        ///
+        /// Provisions shared across envs are only reduced once.
+        #[arg(long, conflicts_with_all = ["env_key", "task_env"])]
+        all: bool,
     },
     /// Activate an environment
     Activate {
@@ -326,15 +444,207 @@ enum EnvsCommands {
         /// Show the environment used by the named task
         #[arg(short, long, value_name = "TASK NAME", conflicts_with = "env_key")]
         task_env: Option<String>,
+        /// Reduce the env and show which provisions contributed to each env var
+        ///
+        /// Note that reduction might require installing ports.
+        #[arg(long)]
+        explain: bool,
+        /// Show the inheritance tree of the env along with the provisions
+        /// each env in the tree contributed
+        #[arg(long, conflicts_with = "explain")]
+        tree: bool,
+    },
+    /// Check if a cooked environment is up to date with the ghjkfile
+    ///
+    /// Prints one of `fresh`, `stale` or `missing`.
+    Status(StatusArgs),
+    /// Remove cooked environments not referenced by the ghjkfile
+    ///
+    /// Also removes dangling env symlinks and task env dirs left
+    /// behind by interrupted task runs.
+    Gc {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show which file a shim in a cooked environment resolves to
+    Which {
+        /// Name of the executable to look up
+        #[arg(value_name = "BIN")]
+        bin: String,
+        /// The environment to look in
+        ///
+        /// If not provided, this will look in the active env.
+        #[arg(value_name = "ENV KEY")]
+        env_key: Option<String>,
+        /// Look in the environment used by the named task
+        #[arg(short, long, value_name = "TASK NAME", conflicts_with = "env_key")]
+        task_env: Option<String>,
     },
 }
 
+#[derive(clap::Parser, Debug)]
+#[clap(name = "status")]
+pub struct StatusArgs {
+    /// Name of the env dir under `.ghjk/envs` to check
+    ///
+    /// If not provided, this will check `$GHJK_ENV` or the default env.
+    #[arg(value_name = "ENV")]
+    pub env: Option<String>,
+    /// Re-cook the env if stale according to the `auto_sync` config
+    ///
+    /// The shell is signaled to reload through `$GHJK_NEXTFILE` once done.
+    #[arg(long)]
+    pub auto_sync: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvStatus {
+    Fresh,
+    Stale,
+    Missing,
+    /// Stale and being re-cooked in the background
+    Syncing,
+    /// Was stale and got re-cooked
+    Synced,
+}
+
+impl std::fmt::Display for EnvStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvStatus::Fresh => write!(f, "fresh"),
+            EnvStatus::Stale => write!(f, "stale"),
+            EnvStatus::Missing => write!(f, "missing"),
+            EnvStatus::Syncing => write!(f, "syncing"),
+            EnvStatus::Synced => write!(f, "synced"),
+        }
+    }
+}
+
+/// Record of the serialization an env dir was cooked from.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookRecord {
+    pub env_key: String,
+    /// Digest of the hashfile of the serialization
+    pub hash_digest: String,
+}
+
+impl CookRecord {
+    pub const FILE_NAME: &'static str = "cooked.json";
+
+    pub async fn from_env_dir(env_dir: &Path) -> Res<Option<Self>> {
+        let path = env_dir.join(Self::FILE_NAME);
+        let raw = match tokio::fs::read(&path).await {
+            Ok(val) => val,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).wrap_err_with(|| format!("error reading {path:?}")),
+        };
+        // treat corrupt records as missing
+        Ok(serde_json::from_slice(&raw).ok())
+    }
+}
+
+/// Checks the env dir against the hashfile without loading the ghjkfile.
+///
+/// Intended to be cheap enough for use from the shell hooks.
+pub async fn env_status(
+    config: &crate::config::Config,
+    ghjkdir_path: &Path,
+    env: Option<&str>,
+) -> Res<EnvStatus> {
+    let env = status_env_name(env);
+    env_dir_status(config, ghjkdir_path, &env).await
+}
+
+/// Like [`env_status`] but re-cooks stale envs if `auto_sync` is enabled.
+///
+/// The re-cook is done by a `ghjk sync` child process which writes to
+/// `$GHJK_NEXTFILE` on success, prompting the shell hooks to reload.
+pub async fn env_status_auto_sync(
+    config: &crate::config::Config,
+    ghjkdir_path: &Path,
+    env: Option<&str>,
+) -> Res<EnvStatus> {
+    use crate::config::AutoSyncMode;
+
+    let env = status_env_name(env);
+    let status = env_dir_status(config, ghjkdir_path, &env).await?;
+    if status != EnvStatus::Stale
+        || config.auto_sync == AutoSyncMode::Off
+        || std::env::var("GHJK_NEXTFILE").is_err()
+    {
+        return Ok(status);
+    }
+
+    let mut cmd = tokio::process::Command::new(
+        std::env::current_exe().wrap_err("error trying to resolve path of current exec")?,
+    );
+    cmd.arg("sync")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        // keep it out of the reach of the shell's job control
+        .process_group(0);
+    if env == "default" {
+        // the default env dir is a symlink so let
+        // the CLI resolve it afresh
+        cmd.env_remove("GHJK_ENV");
+    } else {
+        cmd.arg(&env);
+    }
+    let mut child = cmd.spawn().wrap_err("error spawning sync process")?;
+
+    if config.auto_sync == AutoSyncMode::Background {
+        return Ok(EnvStatus::Syncing);
+    }
+    match tokio::time::timeout(
+        std::time::Duration::from_secs(config.auto_sync_timeout),
+        child.wait(),
+    )
+    .await
+    {
+        Ok(Ok(exit)) if exit.success() => Ok(EnvStatus::Synced),
+        Ok(Ok(exit)) => {
+            warn!(?exit, "auto sync failed");
+            Ok(EnvStatus::Stale)
+        }
+        Ok(Err(err)) => Err(err).wrap_err("error waiting on sync process"),
+        // leave it to finish in the background
+        Err(_) => Ok(EnvStatus::Syncing),
+    }
+}
+
+/// Same resolution as the shell hooks.
+fn status_env_name(env: Option<&str>) -> String {
+    match env {
+        Some(val) => val.to_string(),
+        None => std::env::var("GHJK_ENV").unwrap_or_else(|_| "default".to_string()),
+    }
+}
+
+async fn env_dir_status(
+    config: &crate::config::Config,
+    ghjkdir_path: &Path,
+    env: &str,
+) -> Res<EnvStatus> {
+    let env_dir = ghjkdir_path.join("envs").join(env);
+    if !crate::utils::file_exists(&env_dir).await? {
+        return Ok(EnvStatus::Missing);
+    }
+    let Some(record) = CookRecord::from_env_dir(&env_dir).await? else {
+        return Ok(EnvStatus::Stale);
+    };
+    match crate::host::fresh_hashfile_digest(config, ghjkdir_path).await? {
+        Some(digest) if digest == record.hash_digest => Ok(EnvStatus::Fresh),
+        _ => Ok(EnvStatus::Stale),
+    }
+}
+
 async fn reduce_strange_provisions(
     ecx: &EnvsCtx,
     recipe: &types::EnvRecipe,
 ) -> Res<types::WellKnownEnvRecipe> {
-    use types::{Provision, WellKnownProvision};
-
     // First, try to get TypeScript reduced provisions if callback is available
     let ts_reduced_provisions = if let Some(cb) = ecx.reduce_callback.get() {
         let ts_recipe = cb(recipe.clone())
@@ -344,10 +654,22 @@ async fn reduce_strange_provisions(
     } else {
         recipe.provides.clone()
     };
+    reduce_rust_provisions(ecx, recipe, ts_reduced_provisions).await
+}
 
-    // Group provisions by type for Rust reduction (similar to TypeScript implementation)
-    let mut bins: HashMap<String, Vec<Provision>> = HashMap::new();
-    for provision in &ts_reduced_provisions {
+/// Results of TypeScript reductions keyed by the hash of the reduced bin.
+///
+/// Shared across the envs of `envs cook --all` so that identical
+/// provisions, and the ports they install, get reduced only once.
+type ReductionMemo = HashMap<String, Vec<types::Provision>>;
+
+/// Group provisions by type, keeping the bins in order of first appearance
+/// to keep the last-writer-wins ordering of env vars stable.
+fn bin_provisions(provides: &[types::Provision]) -> IndexMap<String, Vec<types::Provision>> {
+    use types::Provision;
+
+    let mut bins: IndexMap<String, Vec<Provision>> = IndexMap::new();
+    for provision in provides {
         let ty = match provision {
             Provision::WellKnown(well_known) => well_known.provision_type().to_string(),
             Provision::Strange(strange) => {
@@ -361,6 +683,55 @@ async fn reduce_strange_provisions(
         };
         bins.entry(ty).or_default().push(provision.clone());
     }
+    bins
+}
+
+/// Like [`reduce_strange_provisions`] but the TypeScript reduction is done
+/// a bin at a time so that results can be shared with other envs that
+/// have the same provisions.
+async fn reduce_strange_provisions_memo(
+    ecx: &EnvsCtx,
+    recipe: &types::EnvRecipe,
+    memo: &mut ReductionMemo,
+) -> Res<types::WellKnownEnvRecipe> {
+    let ts_reduced_provisions = if let Some(cb) = ecx.reduce_callback.get() {
+        let mut out = vec![];
+        for (ty, items) in bin_provisions(&recipe.provides) {
+            if is_well_known_type(&ty) || ecx.reducer_store.contains_key(&ty) {
+                out.extend(items);
+                continue;
+            }
+            let key = crate::utils::hash_obj(&items);
+            if let Some(reduced) = memo.get(&key) {
+                out.extend(reduced.iter().cloned());
+                continue;
+            }
+            let ts_recipe = cb(types::EnvRecipe {
+                desc: None,
+                provides: items,
+                provenance: None,
+            })
+            .await
+            .wrap_err("error reducing ts provisions")?;
+            out.extend(ts_recipe.provides.iter().cloned());
+            memo.insert(key, ts_recipe.provides);
+        }
+        out
+    } else {
+        recipe.provides.clone()
+    };
+    reduce_rust_provisions(ecx, recipe, ts_reduced_provisions).await
+}
+
+async fn reduce_rust_provisions(
+    ecx: &EnvsCtx,
+    recipe: &types::EnvRecipe,
+    ts_reduced_provisions: Vec<types::Provision>,
+) -> Res<types::WellKnownEnvRecipe> {
+    use types::{Provision, WellKnownProvision};
+
+    // Group provisions by type for Rust reduction (similar to TypeScript implementation)
+    let bins = bin_provisions(&ts_reduced_provisions);
 
     let mut reduced_set = Vec::new();
 
@@ -409,7 +780,7 @@ pub async fn reduce_and_cook_to(
     env_name: Option<&str>,
     env_dir: &Path,
     create_shell_loaders: bool,
-) -> Res<IndexMap<String, String>> {
+) -> Res<(IndexMap<String, String>, types::WellKnownEnvRecipe)> {
     let state: Arc<LoadedState> = scx.get_bb("envs.state");
 
     let recipe = state.config.envs.get(env_key).ok_or_else(|| {
@@ -431,7 +802,7 @@ pub async fn reduce_and_cook_to(
         create_shell_loaders,
     )
     .await?;
-    Ok(env_vars)
+    Ok((env_vars, reduced_recipe))
 }
 
 async fn reduce_and_cook(
@@ -440,11 +811,80 @@ async fn reduce_and_cook(
     state: &LoadedState,
     env_key: &str,
     env_name: Option<&str>,
+) -> Res<()> {
+    let env_dir = ecx.ghjkdir_path.join("envs").join(env_key);
+    reduce_and_cook_to(ecx, scx, env_key, env_name, &env_dir, true).await?;
+    finish_cooked_env(ecx, scx, state, env_key, env_name).await
+}
+
+/// Reduce all the envs in the config and cook them.
+///
+/// Reductions are done one after the other, sharing results across envs,
+/// while the cooking of the reduced envs is done concurrently.
+async fn cook_all_envs(
+    ecx: &EnvsCtx,
+    scx: &crate::systems::SystemsCtx,
+    state: &LoadedState,
+) -> Res<()> {
+    let mut memo = ReductionMemo::new();
+    let mut reduced = vec![];
+    for (env_key, recipe) in &state.config.envs {
+        let env_name = state
+            .key_to_name
+            .get(env_key)
+            .and_then(|names| names.first())
+            .map(|name| name.as_str());
+        let reduced_recipe = reduce_strange_provisions_memo(ecx, recipe, &mut memo)
+            .await
+            .wrap_err_with(|| format!("error reducing env '{}'", env_name.unwrap_or(env_key)))?;
+        reduced.push((env_key.as_str(), env_name, reduced_recipe));
+    }
+    debug!(
+        envs = reduced.len(),
+        reductions = memo.len(),
+        "reduced all envs"
+    );
+
+    futures::future::try_join_all(reduced.iter().map(
+        |&(env_key, env_name, ref reduced_recipe)| async move {
+            let env_dir = ecx.ghjkdir_path.join("envs").join(env_key);
+            posix::cook(
+                ecx,
+                reduced_recipe,
+                env_name.unwrap_or(env_key),
+                &env_dir,
+                true,
+            )
+            .await
+            .wrap_err_with(|| format!("error cooking env '{}'", env_name.unwrap_or(env_key)))?;
+            finish_cooked_env(ecx, scx, state, env_key, env_name).await
+        },
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Write the cook record and the symlinks of a freshly cooked env dir.
+async fn finish_cooked_env(
+    ecx: &EnvsCtx,
+    scx: &crate::systems::SystemsCtx,
+    state: &LoadedState,
+    env_key: &str,
+    env_name: Option<&str>,
 ) -> Res<()> {
     let envs_dir = ecx.ghjkdir_path.join("envs");
     let env_dir = envs_dir.join(env_key);
 
-    reduce_and_cook_to(ecx, scx, env_key, env_name, &env_dir, true).await?;
+    let hash_digest: Arc<String> = scx.get_bb(crate::host::HASH_DIGEST_BB_KEY);
+    tokio::fs::write(
+        env_dir.join(CookRecord::FILE_NAME),
+        serde_json::to_vec_pretty(&CookRecord {
+            env_key: env_key.to_string(),
+            hash_digest: hash_digest.as_ref().clone(),
+        })?,
+    )
+    .await
+    .wrap_err("error writing cook record")?;
 
     // Create symlink for default environment if this cooked env is the configured default
     if let Some(env_name) = env_name {
@@ -464,6 +904,10 @@ async fn reduce_and_cook(
             if named_dir.exists() {
                 tokio::fs::remove_file(&named_dir).await.ok(); // Ignore errors
             }
+            // names of workspace member envs are nested under the member's name
+            if let Some(parent) = named_dir.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
             tokio::fs::symlink(&env_dir, &named_dir).await?;
 
             // Also handle case where the name itself is the default env
@@ -481,21 +925,50 @@ async fn reduce_and_cook(
     Ok(())
 }
 
-async fn list_envs(state: &LoadedState) {
-    for (name, hash) in &state.config.envs_named {
-        // Don't show envs that start with underscore (like TypeScript version)
-        if !name.starts_with('_') {
-            if let Some(env) = state.config.envs.get(hash) {
-                if let Some(desc) = &env.desc {
-                    println!("{}: {}", name, desc);
-                } else {
-                    println!("{}", name);
-                }
+fn list_envs(ecx: &EnvsCtx, state: &LoadedState, json: bool, all: bool) -> Res<()> {
+    // Don't show envs that start with underscore (like TypeScript version)
+    let listed = state
+        .config
+        .envs_named
+        .iter()
+        .filter(|(name, _)| all || !name.starts_with('_'));
+    if !json {
+        for (name, hash) in listed {
+            let desc = state.config.envs.get(hash).and_then(|env| env.desc.as_ref());
+            if let Some(desc) = desc {
+                println!("{}: {}", name, desc);
             } else {
                 println!("{}", name);
             }
         }
+        return Ok(());
     }
+
+    let envs_dir = ecx.ghjkdir_path.join("envs");
+    let listed = listed
+        .map(|(name, hash)| {
+            let recipe = state.config.envs.get(hash);
+            let provides = recipe.map(|env| &env.provides[..]).unwrap_or_default();
+            let provision_counts: IndexMap<String, usize> = bin_provisions(provides)
+                .into_iter()
+                .map(|(ty, items)| (ty, items.len()))
+                .collect();
+            json!({
+                "name": name,
+                "envKey": hash,
+                "desc": recipe.and_then(|env| env.desc.as_ref()),
+                "isDefault": *name == state.config.default_env,
+                "isActive": *name == state.active_env || *hash == state.active_env,
+                "isCooked": envs_dir.join(hash).exists(),
+                "provisionCounts": provision_counts,
+            })
+        })
+        .collect::<Vec<_>>();
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&listed).expect_or_log("json error")
+    );
+    Ok(())
 }
 
 async fn activate_env(env_key: String) -> Res<()> {
@@ -534,6 +1007,7 @@ fn show_env(state: &LoadedState, env_key: &str, env_name: Option<&str>) -> Res<(
         "desc": recipe.desc,
         "envKey": env_key,
         "envNames": env_names,
+        "provenance": recipe.provenance,
     });
     println!(
         "{}",
@@ -542,6 +1016,295 @@ fn show_env(state: &LoadedState, env_key: &str, env_name: Option<&str>) -> Res<(
     Ok(())
 }
 
+fn show_env_tree(state: &LoadedState, env_key: &str, env_name: Option<&str>) -> Res<()> {
+    let recipe = state.config.envs.get(env_key).ok_or_else(|| {
+        if let Some(env_name) = env_name {
+            ferr!("no env found under name '{env_name}'")
+        } else {
+            ferr!("no env found under key '{env_key}'")
+        }
+    })?;
+    let Some(provenance) = &recipe.provenance else {
+        eyre::bail!(
+            "env has no provenance info, re-serialize the ghjkfile with a newer ghjk to get it"
+        );
+    };
+
+    // group the provisions of the env by the env they were declared in
+    let mut by_source: IndexMap<&str, Vec<String>> = IndexMap::new();
+    for (prov, source) in recipe.provides.iter().zip(&provenance.sources) {
+        by_source
+            .entry(source.as_str())
+            .or_default()
+            .push(provision_label(prov));
+    }
+
+    fn render(
+        state: &LoadedState,
+        by_source: &IndexMap<&str, Vec<String>>,
+        name: &str,
+        env_key: &str,
+        prefix: &str,
+        out: &mut String,
+    ) {
+        use std::fmt::Write;
+        let parents = state
+            .config
+            .envs
+            .get(env_key)
+            .and_then(|recipe| recipe.provenance.as_ref())
+            .map(|provenance| &provenance.parents[..])
+            .unwrap_or_default();
+        let provides = by_source.get(name).map(|vec| &vec[..]).unwrap_or_default();
+        let bar = if parents.is_empty() { " " } else { "│" };
+        for label in provides {
+            writeln!(out, "{prefix}{bar}  {label}").expect_or_log("fmt error");
+        }
+        for (idx, parent) in parents.iter().enumerate() {
+            let last = idx == parents.len() - 1;
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            writeln!(out, "{prefix}{branch}{} ({})", parent.name, parent.env_key)
+                .expect_or_log("fmt error");
+            render(
+                state,
+                by_source,
+                &parent.name,
+                &parent.env_key,
+                &format!("{prefix}{indent}"),
+                out,
+            );
+        }
+    }
+
+    let mut out = format!("{} ({env_key})\n", env_name.unwrap_or(&provenance.name));
+    render(state, &by_source, &provenance.name, env_key, "", &mut out);
+    print!("{out}");
+    Ok(())
+}
+
+/// Short description of a provision for display.
+fn provision_label(prov: &types::Provision) -> String {
+    let value = serde_json::to_value(prov).expect_or_log("json error");
+    let ty = value
+        .get("ty")
+        .and_then(|val| val.as_str())
+        .unwrap_or("unknown");
+    let detail = ["key", "taskKey", "setId", "aliasName"]
+        .iter()
+        .find_map(|field| value.get(field).and_then(|val| val.as_str()));
+    match detail {
+        Some(detail) => format!("{ty} {detail}"),
+        None => ty.to_string(),
+    }
+}
+
+async fn explain_env(
+    ecx: &EnvsCtx,
+    state: &LoadedState,
+    env_key: &str,
+    env_name: Option<&str>,
+) -> Res<()> {
+    let recipe = state.config.envs.get(env_key).ok_or_else(|| {
+        if let Some(env_name) = env_name {
+            ferr!("no env found under name '{env_name}'")
+        } else {
+            ferr!("no env found under key '{env_key}'")
+        }
+    })?;
+
+    let reduced_recipe = reduce_strange_provisions(ecx, recipe).await?;
+    let vars = posix::resolve_env_vars(&reduced_recipe)?;
+
+    let env_names = state.key_to_name.get(env_key);
+    let showable = json!({
+        "desc": recipe.desc,
+        "envKey": env_key,
+        "envNames": env_names,
+        "vars": vars,
+        "provides": reduced_recipe.provides,
+    });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&showable).expect_or_log("json error")
+    );
+    Ok(())
+}
+
+/// Task env dirs older than this are assumed to be left over by
+/// interrupted runs and not in use by a running task.
+const TASK_ENV_DIR_GC_AGE: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// File in task env dirs holding the pid of the ghjk process running the task
+/// or, once it's done, the pids of the background hooks it left running.
+pub const TASK_ENV_PID_FILE: &str = "ghjk_task.pid";
+
+/// Prefix of the temp dirs task envs are cooked into.
+///
+/// It's tagged with the ghjkdir so that the gc only
+/// touches the dirs of its own tasks.
+pub fn task_env_dir_prefix(ecx: &EnvsCtx) -> String {
+    let tag = crate::utils::hash_str(&ecx.ghjkdir_path.to_string_lossy());
+    format!("ws_ghjkTaskEnv_{}_", &tag[..12])
+}
+
+/// Whether any of the processes making use of the task env dir is still running.
+async fn task_env_dir_in_use(path: &Path) -> bool {
+    let Ok(raw) = tokio::fs::read_to_string(path.join(TASK_ENV_PID_FILE)).await else {
+        return false;
+    };
+    raw.lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .any(|pid| {
+            matches!(
+                nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None),
+                Ok(()) | Err(nix::errno::Errno::EPERM)
+            )
+        })
+}
+
+async fn gc_envs(ecx: &EnvsCtx, state: &LoadedState, dry_run: bool) -> Res<()> {
+    let envs_dir = ecx.ghjkdir_path.join("envs");
+    let config = &state.config;
+
+    // the env key each symlink is expected to point to
+    fn expected_link_target<'a>(config: &'a EnvsModuleConfig, name: &str) -> Option<&'a str> {
+        if name == "default" {
+            Some(
+                config
+                    .envs_named
+                    .get(&config.default_env)
+                    .unwrap_or(&config.default_env)
+                    .as_str(),
+            )
+        } else {
+            config.envs_named.get(name).map(|key| key.as_str())
+        }
+    }
+
+    let mut removals = vec![];
+    if crate::utils::file_exists(&envs_dir).await? {
+        let mut kept_dirs = std::collections::HashSet::new();
+        let mut links = vec![];
+        let mut read_dir = tokio::fs::read_dir(&envs_dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let file_type = entry.file_type().await?;
+            if file_type.is_symlink() {
+                links.push((name, entry.path()));
+            } else if file_type.is_dir() {
+                if config.envs.contains_key(&name) {
+                    kept_dirs.insert(name);
+                } else if config
+                    .envs_named
+                    .keys()
+                    .any(|named| named.starts_with(&format!("{name}/")))
+                {
+                    // dirs holding the links of workspace member envs
+                    let mut member_dir = tokio::fs::read_dir(entry.path()).await?;
+                    while let Some(entry) = member_dir.next_entry().await? {
+                        let link_name = entry.file_name().to_string_lossy().to_string();
+                        if entry.file_type().await?.is_symlink() {
+                            links.push((format!("{name}/{link_name}"), entry.path()));
+                        } else {
+                            removals.push(entry.path());
+                        }
+                    }
+                } else {
+                    removals.push(entry.path());
+                }
+            }
+        }
+        for (name, path) in links {
+            let target = tokio::fs::read_link(&path).await?;
+            let target_key = target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string());
+            let keep = matches!(
+                (expected_link_target(config, &name), target_key),
+                (Some(expected), Some(actual)) if expected == actual && kept_dirs.contains(&actual)
+            );
+            if !keep {
+                removals.push(path);
+            }
+        }
+    }
+
+    let tmp_dir = std::env::temp_dir();
+    let prefix = task_env_dir_prefix(ecx);
+    match tokio::fs::read_dir(&tmp_dir).await {
+        Ok(mut read_dir) => {
+            while let Ok(Some(entry)) = read_dir.next_entry().await {
+                if !entry.file_name().to_string_lossy().starts_with(&prefix) {
+                    continue;
+                }
+                let Ok(modified) = entry.metadata().await.and_then(|meta| meta.modified()) else {
+                    continue;
+                };
+                let old = modified
+                    .elapsed()
+                    .map(|age| age > TASK_ENV_DIR_GC_AGE)
+                    .unwrap_or(false);
+                if old && !task_env_dir_in_use(&entry.path()).await {
+                    removals.push(entry.path());
+                }
+            }
+        }
+        Err(err) => warn!(?tmp_dir, %err, "error reading temp dir, skipping task env dirs"),
+    }
+
+    for path in removals {
+        if dry_run {
+            println!("would remove {}", path.display());
+            continue;
+        }
+        let res = match tokio::fs::symlink_metadata(&path).await {
+            Ok(meta) if meta.is_dir() => tokio::fs::remove_dir_all(&path).await,
+            Ok(_) => tokio::fs::remove_file(&path).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            warn!(?path, %err, "error removing, skipping");
+            continue;
+        }
+        println!("removed {}", path.display());
+    }
+    Ok(())
+}
+
+async fn which_bin(
+    ecx: &EnvsCtx,
+    bin: &str,
+    env_key: &str,
+    env_name: Option<&str>,
+) -> Res<()> {
+    let env_dir = ecx.ghjkdir_path.join("envs").join(env_key);
+    let Some(shims) = posix::ShimsManifest::from_env_dir(&env_dir).await? else {
+        eyre::bail!(
+            "env '{}' has not been cooked, run `ghjk envs cook` first",
+            env_name.unwrap_or(env_key)
+        );
+    };
+    let Some(entry) = shims.bin.get(bin) else {
+        eyre::bail!(
+            "no executable named '{bin}' found in env '{}'",
+            env_name.unwrap_or(env_key)
+        );
+    };
+    let describe = |target: &posix::ShimTarget| match &target.inst_id {
+        Some(inst_id) => format!("{} (from {inst_id})", target.target.display()),
+        None => format!("{}", target.target.display()),
+    };
+    println!("{}", describe(&entry.provider));
+    for shadowed in &entry.shadows {
+        println!("  shadows {}", describe(shadowed));
+    }
+    Ok(())
+}
+
 async fn detect_shell_path() -> Res<String> {
     if let Ok(path) = std::env::var("SHELL") {
         return Ok(path);
@@ -572,6 +1335,9 @@ fn is_well_known_type(ty: &str) -> bool {
             | "posix.exec"
             | "posix.sharedLib"
             | "posix.headerFile"
+            | "posix.pkgConfig"
+            | "posix.manPage"
+            | "posix.shareDir"
             | "ghjk.ports.Install"
             | "ghjk.shell.Alias"
             | "posix.shell.Completion.bash"
@@ -0,0 +1,153 @@
+//! Running of env hooks outside of the activators.
+//!
+//! The activators run the hooks in the user's shell but task
+//! execution doesn't go through them so the hooks are run from here.
+
+use crate::interlude::*;
+
+use super::{
+    posix::{HOOK_RUNNER_FILE_NAME, HOOK_RUNNER_SCRIPT},
+    types::{HookFailureMode, PosixExecHook, WellKnownEnvRecipe, WellKnownProvision},
+    EnvsCtx,
+};
+
+/// Set on the env of hooks run from here so that tasks invoked
+/// by the hooks don't run the hooks again.
+pub const SKIP_HOOKS_ENV_VAR: &str = "GHJK_SKIP_ENV_HOOKS";
+
+#[derive(Debug, Default)]
+pub struct EnvHooks {
+    pub on_enter: Vec<PosixExecHook>,
+    pub on_exit: Vec<PosixExecHook>,
+}
+
+impl EnvHooks {
+    pub fn from_recipe(recipe: &WellKnownEnvRecipe) -> Self {
+        let mut out = Self::default();
+        for prov in &recipe.provides {
+            match prov {
+                WellKnownProvision::HookOnEnterPosixExec(hook) => out.on_enter.push(hook.clone()),
+                WellKnownProvision::HookOnExitPosixExec(hook) => out.on_exit.push(hook.clone()),
+                _ => {}
+            }
+        }
+        out
+    }
+}
+
+/// If we're running under a hook that was run from here.
+pub fn hooks_skipped() -> bool {
+    std::env::var_os(SKIP_HOOKS_ENV_VAR).is_some()
+}
+
+/// Run the hooks in order, waiting on each unless it's a background hook.
+///
+/// Returns the pids of the background hooks which might still be running.
+pub async fn run_hooks(
+    ecx: &EnvsCtx,
+    hooks: &[PosixExecHook],
+    on_exit: bool,
+    env_vars: &IndexMap<String, String>,
+    working_dir: &Path,
+) -> Res<Vec<u32>> {
+    let mut background_pids = vec![];
+    for hook in hooks {
+        if let Some(pid) = run_hook(ecx, hook, on_exit, env_vars, working_dir).await? {
+            background_pids.push(pid);
+        }
+    }
+    Ok(background_pids)
+}
+
+async fn run_hook(
+    ecx: &EnvsCtx,
+    hook: &PosixExecHook,
+    on_exit: bool,
+    env_vars: &IndexMap<String, String>,
+    working_dir: &Path,
+) -> Res<Option<u32>> {
+    let label = std::iter::once(&hook.program)
+        .chain(&hook.arguments)
+        .join(" ");
+    debug!(hook = %label, on_exit, "running env hook");
+
+    let program = if hook.program == "ghjk" {
+        ecx.gcx.exec_path.clone()
+    } else {
+        PathBuf::from(&hook.program)
+    };
+    let mut cmd = if hook.background {
+        // background hooks might outlive us so it's
+        // left to the runner to enforce the timeout
+        let mut cmd = tokio::process::Command::new("/bin/sh");
+        cmd.arg("-c")
+            .arg(HOOK_RUNNER_SCRIPT)
+            .arg(HOOK_RUNNER_FILE_NAME)
+            .arg(hook.timeout.unwrap_or(0).to_string())
+            .arg(&program);
+        cmd
+    } else {
+        tokio::process::Command::new(&program)
+    };
+    cmd.args(&hook.arguments)
+        .env_clear()
+        .envs(env_vars)
+        .env(SKIP_HOOKS_ENV_VAR, "1")
+        .current_dir(working_dir);
+    if hook.program == "ghjk" {
+        cmd.env("GHJKDIR", &ecx.ghjkdir_path);
+    }
+
+    if hook.background {
+        use std::process::Stdio;
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            // keep it out of the reach of the shell's job control
+            .process_group(0);
+        // failures of background hooks go unreported
+        return match cmd.spawn() {
+            Ok(child) => Ok(child.id()),
+            Err(err) => {
+                debug!(?err, hook = %label, "error spawning background hook");
+                Ok(None)
+            }
+        };
+    }
+
+    cmd.kill_on_drop(true);
+    let res: Result<(), String> = async {
+        let mut child = cmd
+            .spawn()
+            .map_err(|err| format!("failed to spawn: {err}"))?;
+        let status = if let Some(secs) = hook.timeout {
+            let wait = child.wait();
+            match tokio::time::timeout(std::time::Duration::from_secs(secs), wait).await {
+                Ok(status) => status,
+                Err(_) => {
+                    child.kill().await.ok();
+                    return Err(format!("timed out after {secs}s"));
+                }
+            }
+        } else {
+            child.wait().await
+        }
+        .map_err(|err| format!("failed to wait on process: {err}"))?;
+        if !status.success() {
+            return Err(format!("failed with {status}"));
+        }
+        Ok(())
+    }
+    .await;
+
+    let Err(msg) = res else {
+        return Ok(None);
+    };
+    match hook.failure_mode {
+        HookFailureMode::Ignore => debug!(hook = %label, "env hook {msg}"),
+        // abort only applies to onEnter hooks
+        HookFailureMode::Abort if !on_exit => eyre::bail!("env hook {msg}: {label}"),
+        HookFailureMode::Warn | HookFailureMode::Abort => warn!(hook = %label, "env hook {msg}"),
+    }
+    Ok(None)
+}
@@ -1,7 +1,11 @@
 use crate::interlude::*;
 use std::fmt::Write;
 
-use super::{types::WellKnownEnvRecipe, types::WellKnownProvision, EnvsCtx};
+use super::{
+    template::{self, Template},
+    types::{EnvVarMerge, HookFailureMode, PosixExecHook, WellKnownEnvRecipe, WellKnownProvision},
+    EnvsCtx,
+};
 
 type AliasSpec = (String, Vec<String>, Option<String>, Option<Vec<String>>);
 
@@ -21,45 +25,96 @@ pub async fn cook(
     let bin_shim_dir = shim_dir.join("bin");
     let lib_shim_dir = shim_dir.join("lib");
     let include_shim_dir = shim_dir.join("include");
+    let pkg_config_shim_dir = shim_dir.join("pkgconfig");
+    let man_shim_dir = shim_dir.join("man");
+    let share_shim_dir = shim_dir.join("share");
+    let cmake_shim_dir = shim_dir.join("cmake/lib/cmake");
 
     // Create all shim directories concurrently
-    let (_, _, _) = tokio::try_join!(
+    tokio::try_join!(
         tokio::fs::create_dir_all(&bin_shim_dir),
         tokio::fs::create_dir_all(&lib_shim_dir),
-        tokio::fs::create_dir_all(&include_shim_dir)
+        tokio::fs::create_dir_all(&include_shim_dir),
+        tokio::fs::create_dir_all(&pkg_config_shim_dir),
+        tokio::fs::create_dir_all(&man_shim_dir),
+        tokio::fs::create_dir_all(&share_shim_dir),
+        tokio::fs::create_dir_all(&cmake_shim_dir),
     )?;
 
     let mut bin_paths = vec![];
     let mut lib_paths = vec![];
     let mut include_paths = vec![];
+    let mut pkg_config_paths = vec![];
+    let mut man_paths = vec![];
+    let mut share_paths = vec![];
     let mut vars: IndexMap<String, String> = IndexMap::new();
     vars.insert("GHJK_ENV".to_string(), env_key.to_string());
-    let mut on_enter_hooks: Vec<(String, Vec<String>)> = vec![];
-    let mut on_exit_hooks: Vec<(String, Vec<String>)> = vec![];
+    for (key, resolved) in resolve_env_vars(recipe)? {
+        if key == "GHJK_ENV" {
+            eyre::bail!("env var key \"GHJK_ENV\" is reserved and can't be provisioned");
+        }
+        vars.insert(key, resolved.val);
+    }
+    let vars = template::expand_all(
+        &vars,
+        &template::TemplateCtx {
+            ghjk_dir: &ecx.ghjkdir_path,
+            data_dir: &ecx.gcx.config.data_dir,
+            env_dir,
+            env_name: env_key,
+        },
+    )
+    .wrap_err("error interpolating env var values")?;
+    let mut on_enter_hooks: Vec<PosixExecHook> = vec![];
+    let mut on_exit_hooks: Vec<PosixExecHook> = vec![];
     let mut aliases: Vec<AliasSpec> = vec![];
 
     for item in &recipe.provides {
         match item {
-            WellKnownProvision::PosixExec { absolute_path } => {
-                bin_paths.push(absolute_path.clone());
+            WellKnownProvision::PosixExec {
+                absolute_path,
+                inst_id,
+            } => {
+                bin_paths.push((absolute_path.clone(), inst_id.clone()));
+            }
+            WellKnownProvision::PosixSharedLib {
+                absolute_path,
+                inst_id,
+            } => {
+                lib_paths.push((absolute_path.clone(), inst_id.clone()));
+            }
+            WellKnownProvision::PosixHeaderFile {
+                absolute_path,
+                inst_id,
+            } => {
+                include_paths.push((absolute_path.clone(), inst_id.clone()));
             }
-            WellKnownProvision::PosixSharedLib { absolute_path } => {
-                lib_paths.push(absolute_path.clone());
+            WellKnownProvision::PosixPkgConfig {
+                absolute_path,
+                inst_id,
+            } => {
+                pkg_config_paths.push((absolute_path.clone(), inst_id.clone()));
             }
-            WellKnownProvision::PosixHeaderFile { absolute_path } => {
-                include_paths.push(absolute_path.clone());
+            WellKnownProvision::PosixManPage {
+                absolute_path,
+                inst_id,
+            } => {
+                man_paths.push((absolute_path.clone(), inst_id.clone()));
             }
-            WellKnownProvision::PosixEnvVar { key, val } => {
-                if vars.contains_key(key) {
-                    eyre::bail!("env var conflict cooking unix env: key \"{}\" has entries \"{}\" and \"{}\"", key, vars[key], val);
-                }
-                vars.insert(key.clone(), val.clone());
+            WellKnownProvision::PosixShareDir {
+                absolute_path,
+                inst_id,
+            } => {
+                share_paths.push((absolute_path.clone(), inst_id.clone()));
             }
-            WellKnownProvision::HookOnEnterPosixExec { program, arguments } => {
-                on_enter_hooks.push((program.clone(), arguments.clone()));
+            WellKnownProvision::PosixEnvVar { .. } => {
+                // resolved above
             }
-            WellKnownProvision::HookOnExitPosixExec { program, arguments } => {
-                on_exit_hooks.push((program.clone(), arguments.clone()));
+            WellKnownProvision::HookOnEnterPosixExec(hook) => {
+                on_enter_hooks.push(hook.clone());
+            }
+            WellKnownProvision::HookOnExitPosixExec(hook) => {
+                on_exit_hooks.push(hook.clone());
             }
             WellKnownProvision::GhjkPortsInstall { .. } => {
                 // do nothing
@@ -83,10 +138,41 @@ pub async fn cook(
         }
     }
 
-    tokio::try_join!(
-        shim_link_paths(&bin_paths, &bin_shim_dir),
-        shim_link_paths(&lib_paths, &lib_shim_dir),
-        shim_link_paths(&include_paths, &include_shim_dir),
+    // the entries of the data dirs are linked instead of the dirs themselves
+    // so that `shims/share` can stand in for all of them in `$XDG_DATA_DIRS`
+    let share_entries = list_dir_entries(&share_paths).await?;
+    // cmake package configs are found in the `cmake` dir next to the libs
+    let cmake_dirs = lib_paths
+        .iter()
+        .filter_map(|(path, inst_id)| {
+            let dir = if path.file_name().is_some_and(|name| name == "cmake") {
+                path.clone()
+            } else {
+                path.parent()?.join("cmake")
+            };
+            Some((dir, inst_id.clone()))
+        })
+        .unique()
+        .collect::<Vec<_>>();
+    let cmake_entries = list_dir_entries(&cmake_dirs).await?;
+
+    let (
+        bin_shims,
+        lib_shims,
+        include_shims,
+        pkg_config_shims,
+        man_shims,
+        share_shims,
+        cmake_shims,
+        _,
+    ) = tokio::try_join!(
+        shim_link_paths(&bin_paths, &bin_shim_dir, file_shim_name),
+        shim_link_paths(&lib_paths, &lib_shim_dir, file_shim_name),
+        shim_link_paths(&include_paths, &include_shim_dir, file_shim_name),
+        shim_link_paths(&pkg_config_paths, &pkg_config_shim_dir, file_shim_name),
+        shim_link_paths(&man_paths, &man_shim_dir, man_shim_name),
+        shim_link_paths(&share_entries, &share_shim_dir, file_shim_name),
+        shim_link_paths(&cmake_entries, &cmake_shim_dir, file_shim_name),
         async {
             tokio::fs::write(
                 env_dir.join("recipe.json"),
@@ -96,6 +182,42 @@ pub async fn cook(
             .wrap_err("failed to write recipe.json")
         }
     )?;
+    let shims = ShimsManifest {
+        bin: bin_shims,
+        lib: lib_shims,
+        include: include_shims,
+        pkg_config: pkg_config_shims,
+        man: man_shims,
+        share: share_shims,
+        cmake: cmake_shims,
+    };
+    for (kind, entries) in [
+        ("bin", &shims.bin),
+        ("lib", &shims.lib),
+        ("include", &shims.include),
+        ("pkgconfig", &shims.pkg_config),
+        ("man", &shims.man),
+        ("share", &shims.share),
+        ("cmake", &shims.cmake),
+    ] {
+        for (name, entry) in entries {
+            if !entry.shadows.is_empty() {
+                warn!(
+                    shim = %name,
+                    kind,
+                    target = ?entry.provider.target,
+                    shadowed = ?entry.shadows.iter().map(|sh| &sh.target).collect::<Vec<_>>(),
+                    "shim conflict, using the earlier provision"
+                );
+            }
+        }
+    }
+    tokio::fs::write(
+        env_dir.join(ShimsManifest::FILE_NAME),
+        serde_json::to_string_pretty(&shims)?,
+    )
+    .await
+    .wrap_err("failed to write shims.json")?;
 
     let ld_library_env = match std::env::consts::OS {
         "macos" => "DYLD_LIBRARY_PATH",
@@ -109,8 +231,26 @@ pub async fn cook(
         ld_library_env.to_string() => env_dir.join("shims/lib"),
         "C_INCLUDE_PATH".to_string() => env_dir.join("shims/include"),
         "CPLUS_INCLUDE_PATH".to_string() => env_dir.join("shims/include"),
+        "PKG_CONFIG_PATH".to_string() => env_dir.join("shims/pkgconfig"),
+        "MANPATH".to_string() => env_dir.join("shims/man"),
+        "XDG_DATA_DIRS".to_string() => env_dir.join("shims/share"),
+        // cmake looks for package configs under `lib/cmake` of the prefixes
+        "CMAKE_PREFIX_PATH".to_string() => env_dir.join("shims/cmake"),
     };
 
+    let ghjk_dir_str = ecx.ghjkdir_path.to_string_lossy();
+    let data_dir_str = ecx.gcx.config.data_dir.to_string_lossy();
+    let rendered_vars: IndexMap<String, String> = vars
+        .iter()
+        .map(|(key, tmpl)| (key.clone(), tmpl.render(&ghjk_dir_str, &data_dir_str)))
+        .collect();
+
+    if ecx.gcx.config.shims == crate::config::ShimsMode::Wrapper {
+        write_wrapper_shims(&shims.bin, &bin_shim_dir, &rendered_vars, &path_vars)
+            .await
+            .wrap_err("error writing wrapper shims")?;
+    }
+
     if create_shell_loaders {
         write_activators(
             ecx,
@@ -126,7 +266,7 @@ pub async fn cook(
     }
 
     // Combine vars and path_vars to return all environment variables
-    let mut env_vars = vars;
+    let mut env_vars = rendered_vars;
     env_vars.extend(
         path_vars
             .into_iter()
@@ -135,61 +275,393 @@ pub async fn cook(
     Ok(env_vars)
 }
 
-async fn shim_link_paths(target_paths: &[PathBuf], shim_dir: &Path) -> Res<()> {
-    let mut shims: HashMap<String, PathBuf> = HashMap::new();
+/// Value to assume for path vars that are unset when prepending the
+/// env's shim dirs so that activation doesn't hide the system defaults.
+pub fn path_var_fallback(key: &str) -> Option<&'static str> {
+    match key {
+        // as specified by the XDG base directory spec
+        "XDG_DATA_DIRS" => Some("/usr/local/share:/usr/share"),
+        // an empty entry makes man search its default paths
+        "MANPATH" => Some(""),
+        _ => None,
+    }
+}
 
-    for path in target_paths {
-        let path_str = path.to_str().ok_or_else(|| ferr!("invalid path"))?;
-        if path_str.contains('*') {
-            for entry in glob::glob(path_str)? {
-                let entry = entry?;
-                let file_name = entry
-                    .file_name()
-                    .ok_or_else(|| ferr!("no file name"))?
-                    .to_str()
-                    .unwrap()
-                    .to_string();
-                if shims.contains_key(&file_name) {
-                    eyre::bail!("duplicate shim found for file: {}", file_name);
-                }
-                let shim_path = shim_dir.join(&file_name);
-                if tokio::fs::try_exists(&shim_path).await? {
-                    tokio::fs::remove_file(&shim_path).await?;
-                }
-                tokio::fs::symlink(&entry, &shim_path).await?;
-                shims.insert(file_name, shim_path);
+/// The final value of an env var along with the provisions that
+/// went into it.
+#[derive(Debug, Serialize)]
+pub struct ResolvedEnvVar {
+    pub val: String,
+    pub contributions: Vec<EnvVarContribution>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvVarContribution {
+    /// Index of the provision in the reduced recipe
+    pub provision_idx: usize,
+    pub val: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge: Option<EnvVarMerge>,
+}
+
+/// Apply the `posix.envVar` provisions of the recipe in order, merging
+/// values that target the same key according to their [`EnvVarMerge`] policy.
+pub fn resolve_env_vars(recipe: &WellKnownEnvRecipe) -> Res<IndexMap<String, ResolvedEnvVar>> {
+    let mut out: IndexMap<String, ResolvedEnvVar> = IndexMap::new();
+    for (idx, item) in recipe.provides.iter().enumerate() {
+        let WellKnownProvision::PosixEnvVar { key, val, merge } = item else {
+            continue;
+        };
+        let contribution = EnvVarContribution {
+            provision_idx: idx,
+            val: val.clone(),
+            merge: merge.clone(),
+        };
+        let Some(resolved) = out.get_mut(key) else {
+            out.insert(
+                key.clone(),
+                ResolvedEnvVar {
+                    val: val.clone(),
+                    contributions: vec![contribution],
+                },
+            );
+            continue;
+        };
+        if matches!(merge, Some(EnvVarMerge::Error))
+            || resolved
+                .contributions
+                .iter()
+                .any(|prev| matches!(prev.merge, Some(EnvVarMerge::Error)))
+        {
+            eyre::bail!(
+                "env var conflict cooking unix env: key \"{key}\" has entries \"{}\" and \"{val}\"",
+                resolved.val
+            );
+        }
+        resolved.val = match merge {
+            None | Some(EnvVarMerge::Override) => val.clone(),
+            Some(EnvVarMerge::Prepend { separator }) => {
+                format!("{val}{separator}{}", resolved.val)
+            }
+            Some(EnvVarMerge::Append { separator }) => {
+                format!("{}{separator}{val}", resolved.val)
+            }
+            Some(EnvVarMerge::Error) => unreachable!(),
+        };
+        resolved.contributions.push(contribution);
+    }
+    Ok(out)
+}
+
+/// Record of the shims linked into an env dir.
+///
+/// Written out next to the activators to support `ghjk envs which`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShimsManifest {
+    pub bin: IndexMap<String, ShimEntry>,
+    pub lib: IndexMap<String, ShimEntry>,
+    pub include: IndexMap<String, ShimEntry>,
+    #[serde(default)]
+    pub pkg_config: IndexMap<String, ShimEntry>,
+    #[serde(default)]
+    pub man: IndexMap<String, ShimEntry>,
+    #[serde(default)]
+    pub share: IndexMap<String, ShimEntry>,
+    #[serde(default)]
+    pub cmake: IndexMap<String, ShimEntry>,
+}
+
+impl ShimsManifest {
+    pub const FILE_NAME: &'static str = "shims.json";
+
+    pub async fn from_env_dir(env_dir: &Path) -> Res<Option<Self>> {
+        let path = env_dir.join(Self::FILE_NAME);
+        let raw = match tokio::fs::read(&path).await {
+            Ok(val) => val,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).wrap_err_with(|| format!("error reading {path:?}")),
+        };
+        Ok(Some(
+            serde_json::from_slice(&raw).wrap_err_with(|| format!("error parsing {path:?}"))?,
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShimTarget {
+    pub target: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inst_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShimEntry {
+    #[serde(flatten)]
+    pub provider: ShimTarget,
+    /// Files with the same name from later provisions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shadows: Vec<ShimTarget>,
+}
+
+fn file_shim_name(path: &Path) -> Res<String> {
+    Ok(path
+        .file_name()
+        .ok_or_else(|| ferr!("no file name"))?
+        .to_str()
+        .unwrap()
+        .to_string())
+}
+
+/// Man pages are placed under the `man<section>` dir they came from
+/// or, failing that, the section implied by their extension.
+fn man_shim_name(path: &Path) -> Res<String> {
+    let file_name = file_shim_name(path)?;
+    let section_dir = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .and_then(|name| name.to_str())
+        .filter(|name| name.starts_with("man") && name.len() > 3);
+    let section_dir = match section_dir {
+        Some(dir) => dir.to_string(),
+        None => {
+            // foo.1 or foo.1.gz
+            let section = file_name
+                .trim_end_matches(".gz")
+                .rsplit_once('.')
+                .and_then(|(_, ext)| ext.chars().next())
+                .filter(|ch| ch.is_ascii_alphanumeric())
+                .ok_or_else(|| ferr!("unable to determine section of man page at {path:?}"))?;
+            format!("man{section}")
+        }
+    };
+    Ok(format!("{section_dir}/{file_name}"))
+}
+
+/// Lists the entries of the given dirs in order, skipping the
+/// dirs that don't exist.
+async fn list_dir_entries(
+    dirs: &[(PathBuf, Option<String>)],
+) -> Res<Vec<(PathBuf, Option<String>)>> {
+    let mut out = vec![];
+    for (dir, inst_id) in dirs {
+        let mut read_dir = match tokio::fs::read_dir(dir).await {
+            Ok(val) => val,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+                ) =>
+            {
+                continue
             }
+            Err(err) => return Err(err).wrap_err_with(|| format!("error reading dir at {dir:?}")),
+        };
+        let mut entries = vec![];
+        while let Some(entry) = read_dir.next_entry().await? {
+            entries.push(entry.path());
+        }
+        entries.sort();
+        out.extend(entries.into_iter().map(|path| (path, inst_id.clone())));
+    }
+    Ok(out)
+}
+
+/// Links the target paths into the shim dir, expanding any globs.
+///
+/// When multiple targets share a file name, the one from the earliest
+/// provision wins, similar to how lookups through `$PATH` resolve.
+/// The later ones are recorded as shadowed.
+async fn shim_link_paths(
+    target_paths: &[(PathBuf, Option<String>)],
+    shim_dir: &Path,
+    shim_name: fn(&Path) -> Res<String>,
+) -> Res<IndexMap<String, ShimEntry>> {
+    let mut shims: IndexMap<String, ShimEntry> = IndexMap::new();
+
+    for (path, inst_id) in target_paths {
+        let path_str = path.to_str().ok_or_else(|| ferr!("invalid path"))?;
+        let entries = if path_str.contains('*') {
+            glob::glob(path_str)?.collect::<Result<Vec<_>, _>>()?
         } else {
-            let file_name = path
-                .file_name()
-                .ok_or_else(|| ferr!("no file name"))?
-                .to_str()
-                .unwrap()
-                .to_string();
-            if shims.contains_key(&file_name) {
-                eyre::bail!("duplicate shim found for file: {}", file_name);
+            vec![path.clone()]
+        };
+        for entry in entries {
+            let file_name = shim_name(&entry)?;
+            let target = ShimTarget {
+                target: entry,
+                inst_id: inst_id.clone(),
+            };
+            if let Some(existing) = shims.get_mut(&file_name) {
+                existing.shadows.push(target);
+                continue;
             }
             let shim_path = shim_dir.join(&file_name);
+            if let Some(parent) = shim_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
             if tokio::fs::try_exists(&shim_path).await? {
                 tokio::fs::remove_file(&shim_path).await?;
             }
-            tokio::fs::symlink(path, &shim_path).await?;
-            shims.insert(file_name, shim_path);
+            tokio::fs::symlink(&target.target, &shim_path).await?;
+            shims.insert(
+                file_name,
+                ShimEntry {
+                    provider: target,
+                    shadows: vec![],
+                },
+            );
         }
     }
 
+    Ok(shims)
+}
+
+/// Replaces the executable symlinks with wrapper scripts that set up
+/// the env before exec'ing the target.
+///
+/// This makes the shims usable outside of an activated shell.
+async fn write_wrapper_shims(
+    bin_shims: &IndexMap<String, ShimEntry>,
+    shim_dir: &Path,
+    env_vars: &IndexMap<String, String>,
+    path_vars: &IndexMap<String, PathBuf>,
+) -> Res<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let quote = |val: &str| format!("'{}'", val.replace("'", "'\\''"));
+
+    let mut preamble = String::new();
+    writeln!(preamble, "#!/bin/sh")?;
+    writeln!(preamble, "# generated by ghjk")?;
+    for (key, val) in env_vars {
+        writeln!(preamble, "export {key}={}", quote(val))?;
+    }
+    for (key, dir) in path_vars {
+        let dir = quote(&dir.to_string_lossy());
+        let prepend = match path_var_fallback(key) {
+            Some(fallback) => format!(r#"{dir}":${{{key}-{fallback}}}""#),
+            None => format!(r#"{dir}"${{{key}:+:${key}}}""#),
+        };
+        // avoid growing the path on nested invocations
+        writeln!(
+            preamble,
+            r#"case ":${{{key}-}}:" in *:{dir}:*) ;; *) export {key}={prepend} ;; esac"#
+        )?;
+    }
+
+    for (name, entry) in bin_shims {
+        let shim_path = shim_dir.join(name);
+        let script = format!(
+            "{preamble}exec {} \"$@\"\n",
+            quote(&entry.provider.target.to_string_lossy())
+        );
+        tokio::fs::remove_file(&shim_path).await?;
+        tokio::fs::write(&shim_path, script).await?;
+        tokio::fs::set_permissions(&shim_path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
     Ok(())
 }
 
+/// Name of the script used to run hooks with a timeout or in the background
+pub(super) const HOOK_RUNNER_FILE_NAME: &str = "hook_runner.sh";
+
+/// Usage: `hook_runner.sh [--background] <timeout secs, 0 for none> <program> [args...]`
+pub(super) const HOOK_RUNNER_SCRIPT: &str = r#"#!/bin/sh
+# runs a ghjk env hook
+if [ "$1" = "--background" ]; then
+    shift
+    /bin/sh "$0" "$@" </dev/null >/dev/null 2>&1 &
+    exit 0
+fi
+secs="$1"
+shift
+if [ "$secs" = "0" ]; then
+    exec "$@"
+fi
+# the hook is put in its own process group so that any
+# processes it spawns can be brought down along with it
+if command -v setsid >/dev/null 2>&1; then
+    setsid "$@" </dev/null &
+else
+    # job control gives each job its own group
+    set -m 2>/dev/null
+    "$@" </dev/null &
+    set +m
+fi
+pid=$!
+(sleep "$secs" >/dev/null 2>&1 && { kill -- "-$pid" 2>/dev/null || kill "$pid" 2>/dev/null; } && echo "ghjk: hook timed out after ${secs}s: $*" >&2) &
+watcher=$!
+wait "$pid"
+status=$?
+kill "$watcher" 2>/dev/null
+exit "$status"
+"#;
+
+/// A hook ready to be written into the activators.
+struct ActivatorHook {
+    /// The single quoted command words to run
+    cmd: String,
+    /// The single quoted command words of the hook used in failure messages
+    label: String,
+    background: bool,
+    failure_mode: HookFailureMode,
+}
+
+impl ActivatorHook {
+    fn failure_mode(&self, on_exit: bool) -> HookFailureMode {
+        match self.failure_mode {
+            // activation can't be aborted when exiting
+            HookFailureMode::Abort if on_exit => HookFailureMode::Warn,
+            // the runner doesn't report failures of background hooks
+            _ if self.background => HookFailureMode::Ignore,
+            mode => mode,
+        }
+    }
+
+    fn posix_line(&self, on_exit: bool) -> String {
+        let Self { cmd, label, .. } = self;
+        match self.failure_mode(on_exit) {
+            HookFailureMode::Ignore => format!("{cmd} || :"),
+            HookFailureMode::Warn => {
+                format!(r#"{cmd} || echo "ghjk: hook failed with status $?:" {label} >&2"#)
+            }
+            HookFailureMode::Abort => format!(
+                r#"{cmd} || {{ echo "ghjk: hook failed with status $?, aborting activation:" {label} >&2; ghjk_deactivate; return 1; }}"#
+            ),
+        }
+    }
+
+    fn fish_line(&self, on_exit: bool) -> String {
+        let Self { cmd, label, .. } = self;
+        match self.failure_mode(on_exit) {
+            HookFailureMode::Ignore => format!("{cmd}; or true"),
+            HookFailureMode::Warn => {
+                format!(r#"{cmd}; or echo "ghjk: hook failed with status $status:" {label} >&2"#)
+            }
+            HookFailureMode::Abort => format!(
+                r#"{cmd}; or begin; echo "ghjk: hook failed with status $status, aborting activation:" {label} >&2; ghjk_deactivate; return 1; end"#
+            ),
+        }
+    }
+}
+
+fn quote_posix_word(word: &str) -> String {
+    format!("'{}'", word.replace("\\", "\\\\").replace("'", "'\\''"))
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn write_activators(
     ecx: &EnvsCtx,
     reduced_recipe: &WellKnownEnvRecipe,
     env_dir: &Path,
-    env_vars: &IndexMap<String, String>,
+    env_vars: &IndexMap<String, Template>,
     path_vars: &IndexMap<String, PathBuf>,
-    on_enter_hooks: &[(String, Vec<String>)],
-    on_exit_hooks: &[(String, Vec<String>)],
+    on_enter_hooks: &[PosixExecHook],
+    on_exit_hooks: &[PosixExecHook],
     aliases: &[AliasSpec],
 ) -> Res<()> {
     let ghjk_dir_var = "_ghjk_dir";
@@ -210,44 +682,64 @@ async fn write_activators(
     }
 
     let ghjk_shim_name = "__ghjk_shim";
-    let on_enter_hooks_escaped: Vec<String> = on_enter_hooks
-        .iter()
-        .map(|(cmd, args)| {
-            let mut parts = std::iter::once(cmd.clone())
-                .chain(args.clone())
-                .collect::<Vec<_>>();
-            if let Some(first) = parts.get_mut(0) {
-                if first == "ghjk" {
-                    *first = ghjk_shim_name.to_string();
-                }
-            }
-            parts
-                .into_iter()
-                .map(|t| t.replace("\\", "\\\\").replace("'", "'\\''"))
-                .map(|t| format!("'{}'", t))
+    let hook_runner_path = env_dir.join(HOOK_RUNNER_FILE_NAME);
+    let activator_hook = |hook: &PosixExecHook| {
+        let words = std::iter::once(&hook.program)
+            .chain(&hook.arguments)
+            .map(|word| quote_posix_word(word))
+            .collect::<Vec<_>>();
+        let shimmed = |program: String| {
+            std::iter::once(program)
+                .chain(words.iter().skip(1).cloned())
                 .collect::<Vec<_>>()
                 .join(" ")
-        })
-        .collect();
-    let on_exit_hooks_escaped: Vec<String> = on_exit_hooks
+        };
+        let cmd = if hook.program == "ghjk" {
+            shimmed(ghjk_shim_name.to_string())
+        } else {
+            words.join(" ")
+        };
+        // the runner is a separate process and can't make use of the shim function
+        let runner_cmd = (hook.timeout.is_some() || hook.background).then(|| {
+            let program = if hook.program == "ghjk" {
+                format!(
+                    "'env' {} {}",
+                    quote_posix_word(&format!("GHJKDIR={ghjk_dir_str}")),
+                    quote_posix_word(&ghjk_exec_path)
+                )
+            } else {
+                words[0].clone()
+            };
+            format!(
+                "'/bin/sh' {}{} '{}' {}",
+                quote_posix_word(&hook_runner_path.to_string_lossy()),
+                if hook.background {
+                    " '--background'"
+                } else {
+                    ""
+                },
+                hook.timeout.unwrap_or(0),
+                shimmed(program)
+            )
+        });
+        ActivatorHook {
+            cmd: runner_cmd.unwrap_or(cmd),
+            label: words.join(" "),
+            background: hook.background,
+            failure_mode: hook.failure_mode,
+        }
+    };
+    if on_enter_hooks
         .iter()
-        .map(|(cmd, args)| {
-            let mut parts = std::iter::once(cmd.clone())
-                .chain(args.clone())
-                .collect::<Vec<_>>();
-            if let Some(first) = parts.get_mut(0) {
-                if first == "ghjk" {
-                    *first = ghjk_shim_name.to_string();
-                }
-            }
-            parts
-                .into_iter()
-                .map(|t| t.replace("\\", "\\\\").replace("'", "'\\''"))
-                .map(|t| format!("'{}'", t))
-                .collect::<Vec<_>>()
-                .join(" ")
-        })
-        .collect();
+        .chain(on_exit_hooks)
+        .any(|hook| hook.timeout.is_some() || hook.background)
+    {
+        tokio::fs::write(&hook_runner_path, HOOK_RUNNER_SCRIPT)
+            .await
+            .wrap_err("error writing hook runner")?;
+    }
+    let on_enter_hooks: Vec<ActivatorHook> = on_enter_hooks.iter().map(&activator_hook).collect();
+    let on_exit_hooks: Vec<ActivatorHook> = on_exit_hooks.iter().map(&activator_hook).collect();
 
     // Collect completion scripts and write them next to activators
     let mut bash_comp = String::new();
@@ -302,8 +794,8 @@ async fn write_activators(
             .map(|p| p.to_string_lossy().to_string()),
         env_vars,
         &path_vars_replaced,
-        &on_enter_hooks_escaped,
-        &on_exit_hooks_escaped,
+        &on_enter_hooks,
+        &on_exit_hooks,
         aliases,
         ghjk_dir_var,
         data_dir_var,
@@ -315,8 +807,8 @@ async fn write_activators(
         &data_dir_str,
         env_vars,
         &path_vars_replaced,
-        &on_enter_hooks_escaped,
-        &on_exit_hooks_escaped,
+        &on_enter_hooks,
+        &on_exit_hooks,
         aliases,
         ghjk_dir_var,
         data_dir_var,
@@ -341,10 +833,10 @@ fn build_posix_script(
     data_dir_str: &str,
     bash_comp_path: Option<String>,
     zsh_comp_path: Option<String>,
-    env_vars: &IndexMap<String, String>,
+    env_vars: &IndexMap<String, Template>,
     path_vars: &IndexMap<String, String>,
-    on_enter_hooks: &[String],
-    on_exit_hooks: &[String],
+    on_enter_hooks: &[ActivatorHook],
+    on_exit_hooks: &[ActivatorHook],
     aliases: &[AliasSpec],
     ghjk_dir_var: &str,
     data_dir_var: &str,
@@ -387,7 +879,35 @@ export GHJK_CLEANUP_POSIX="";
 # so that we can restore them on cleanup
 "#
     )?;
-    for (key, val) in env_vars {
+    for (key, tmpl) in env_vars {
+        let Some(val) = tmpl.as_literal() else {
+            // values that refer to ghjk dirs are computed at activation
+            // and the cleanup compares against that computed value
+            let word = tmpl.posix_word(ghjk_dir_var, data_dir_var);
+            writeln!(buf, r#"_ghjk_val={word};"#)?;
+            // escape the single quotes of the computed value like we do
+            // for the literals below, the trailing dot keeps command
+            // substitution from eating trailing newlines
+            writeln!(
+                buf,
+                r#"_ghjk_val_esc=$(printf '%s.' "$_ghjk_val" | sed "s/'/'\\\\''/g");"#
+            )?;
+            writeln!(buf, r#"_ghjk_val_esc="${{_ghjk_val_esc%.}}";"#)?;
+            write!(
+                buf,
+                r#"GHJK_CLEANUP_POSIX=$GHJK_CLEANUP_POSIX'[ "${{{key}-}}" = '\'"$_ghjk_val_esc"\'' ] && '"#
+            )?;
+            write!(buf, r#"$([ -z "${{{key}+x}}" ] "#)?;
+            write!(buf, r#"&& echo 'unset {key};' "#)?;
+            writeln!(
+                buf,
+                r#"|| echo 'export {key}='\'"${{{key}:-unreachable}}""';");"#
+            )?;
+            writeln!(buf, r#"export {key}="$_ghjk_val";"#)?;
+            writeln!(buf, r#"unset _ghjk_val _ghjk_val_esc;"#)?;
+            writeln!(buf)?;
+            continue;
+        };
         let safe_val = val.replace("\\", "\\\\").replace("'", "'\\''");
         // this avoids triggering unbound variable if -e is set
         // by defaulting to a value that's guaranteed to
@@ -459,7 +979,8 @@ export GHJK_CLEANUP_POSIX="";
         // FIXME: we're allowing expansion in the value to allow
         // readable $ghjkDirVar usage
         // (for now safe since all paths are created within ghjk)
-        writeln!(buf, r#"export {key}="{safe_val}:${{{key}-}}";"#)?;
+        let fallback = path_var_fallback(key).unwrap_or_default();
+        writeln!(buf, r#"export {key}="{safe_val}:${{{key}-{fallback}}}";"#)?;
         writeln!(buf)?;
     }
     let ghjk_shim = ghjk_shim_posix(ghjk_dir_str, ghjk_exec_path, ghjk_shim_name);
@@ -542,8 +1063,8 @@ case "$-" in
     # on enter hooks
 "#
     )?;
-    for line in on_enter_hooks {
-        writeln!(buf, "        {line}")?;
+    for hook in on_enter_hooks {
+        writeln!(buf, "        {}", hook.posix_line(false))?;
     }
     writeln!(
         buf,
@@ -551,7 +1072,8 @@ case "$-" in
     # on exit hooks
 "#
     )?;
-    for line in on_exit_hooks {
+    for hook in on_exit_hooks {
+        let line = hook.posix_line(true).replace("'", "'\\''");
         writeln!(
             buf,
             "        GHJK_CLEANUP_POSIX=$GHJK_CLEANUP_POSIX'{line};';"
@@ -610,10 +1132,10 @@ esac
 fn build_fish_script(
     ghjk_dir_str: &str,
     data_dir_str: &str,
-    env_vars: &IndexMap<String, String>,
+    env_vars: &IndexMap<String, Template>,
     path_vars: &IndexMap<String, String>,
-    on_enter_hooks: &[String],
-    on_exit_hooks: &[String],
+    on_enter_hooks: &[ActivatorHook],
+    on_exit_hooks: &[ActivatorHook],
     aliases: &[AliasSpec],
     ghjk_dir_var: &str,
     data_dir_var: &str,
@@ -647,7 +1169,23 @@ set {data_dir_var} "{data_dir_str}"
 # so that we can restore them on cleanup
 "#
     )?;
-    for (key, val) in env_vars {
+    for (key, tmpl) in env_vars {
+        let Some(val) = tmpl.as_literal() else {
+            let word = tmpl.fish_word(ghjk_dir_var, data_dir_var);
+            writeln!(buf, r#"set --local _ghjk_val {word};"#)?;
+            write!(
+                buf,
+                r#"set --global --append GHJK_CLEANUP_FISH 'test "${key}" = '(string escape -- "$_ghjk_val")'; and '"#
+            )?;
+            writeln!(
+                buf,
+                r#"(if set -q {key}; echo 'set --global --export {key} \''"${key}""';"; else; echo 'set -e {key};'; end;);"#
+            )?;
+            writeln!(buf, r#"set --global --export {key} "$_ghjk_val";"#)?;
+            writeln!(buf, r#"set --erase _ghjk_val;"#)?;
+            writeln!(buf)?;
+            continue;
+        };
         let safe_val = val.replace("\\", "\\\\").replace("'", "'\\''");
         // read the comments from the posix version of this section
         // the fish version is notably simpler since
@@ -679,9 +1217,21 @@ set {data_dir_var} "{data_dir_str}"
             buf,
             r#"set --global --append GHJK_CLEANUP_FISH 'set --global --export --path {key} (string match --invert --regex \''"^{safe_val}"'\' ${key});';"#
         )?;
+        if let Some(fallback) = path_var_fallback(key) {
+            let fallback = fallback
+                .split(':')
+                .map(|path| format!("'{path}'"))
+                .join(" ");
+            writeln!(
+                buf,
+                r#"set --query {key}; or set --global --export --path {key} {fallback};"#
+            )?;
+        }
+        // `--path` makes sure vars fish doesn't treat as path vars by name
+        // (like `XDG_DATA_DIRS`) get exported colon separated
         writeln!(
             buf,
-            r#"set --global --export --prepend {key} "{safe_val}";"#
+            r#"set --global --export --path --prepend {key} "{safe_val}";"#
         )?;
         writeln!(buf)?;
     }
@@ -768,8 +1318,8 @@ if status is-interactive;
     # on enter hooks
 "#
     )?;
-    for line in on_enter_hooks {
-        writeln!(buf, "    {line}")?;
+    for hook in on_enter_hooks {
+        writeln!(buf, "    {}", hook.fish_line(false))?;
     }
     writeln!(
         buf,
@@ -777,7 +1327,11 @@ if status is-interactive;
     # on exit hooks
 "#
     )?;
-    for line in on_exit_hooks {
+    for hook in on_exit_hooks {
+        let line = hook
+            .fish_line(true)
+            .replace("\\", "\\\\")
+            .replace("'", "\\'");
         writeln!(
             buf,
             "    set --global --append GHJK_CLEANUP_FISH '{line};';"
@@ -0,0 +1,119 @@
+use crate::interlude::*;
+
+use super::types::{
+    DynEnvVarProvision, DynEnvVarSource, GitField, Provision, ProvisionReducer, WellKnownProvision,
+};
+
+/// Values already computed in this process, shared across envs
+/// so that the same source isn't evaluated once per env.
+pub type DynEnvVarCache = DHashMap<DynEnvVarSource, String>;
+
+/// This reducer computes the values of `posix.envVarCmd`, `posix.envVarFile`
+/// and `posix.envVarGit` provisions without going through a task.
+pub fn dyn_env_var_reducer(gcx: Arc<GhjkCtx>, cache: Arc<DynEnvVarCache>) -> ProvisionReducer {
+    Box::new(move |provisions: Vec<Provision>| {
+        let gcx = gcx.clone();
+        let cache = cache.clone();
+        async move {
+            let working_dir = gcx
+                .config
+                .ghjkfile
+                .as_ref()
+                .and_then(|path| path.parent())
+                .map(|path| path.to_path_buf())
+                .unwrap_or_else(|| PathBuf::from("."));
+
+            let mut output = Vec::with_capacity(provisions.len());
+            for provision in provisions {
+                let prov: DynEnvVarProvision = match provision {
+                    Provision::Strange(val) => serde_json::from_value(val.clone())
+                        .wrap_err_with(|| format!("error parsing dynamic env var: {val}"))?,
+                    Provision::WellKnown(prov) => {
+                        eyre::bail!("unexpected provision for dynamic env var: {prov:?}")
+                    }
+                };
+                let val = if let Some(val) = cache.get(&prov.source) {
+                    val.clone()
+                } else {
+                    let val = eval_source(&prov.source, &working_dir)
+                        .await
+                        .wrap_err_with(|| format!("error evaluating env var: {}", prov.key))?;
+                    cache.insert(prov.source.clone(), val.clone());
+                    val
+                };
+                output.push(WellKnownProvision::PosixEnvVar {
+                    key: prov.key,
+                    val,
+                    merge: None,
+                });
+            }
+            Ok(output)
+        }
+        .boxed()
+    })
+}
+
+async fn eval_source(source: &DynEnvVarSource, working_dir: &Path) -> Res<String> {
+    match source {
+        DynEnvVarSource::Cmd {
+            program,
+            arguments,
+            working_dir: cmd_dir,
+        } => {
+            let working_dir = join_working_dir(working_dir, cmd_dir.as_deref());
+            cmd_output(program, arguments, &working_dir).await
+        }
+        DynEnvVarSource::File { path } => {
+            let path = working_dir.join(path);
+            let contents = tokio::fs::read_to_string(&path)
+                .await
+                .wrap_err_with(|| format!("error reading file at {path:?}"))?;
+            Ok(contents.trim_end_matches(['\n', '\r']).to_string())
+        }
+        DynEnvVarSource::Git {
+            field,
+            working_dir: git_dir,
+        } => {
+            let working_dir = join_working_dir(working_dir, git_dir.as_deref());
+            let args: &[&str] = match field {
+                GitField::Commit => &["rev-parse", "HEAD"],
+                GitField::ShortCommit => &["rev-parse", "--short", "HEAD"],
+                GitField::Branch => &["rev-parse", "--abbrev-ref", "HEAD"],
+            };
+            cmd_output("git", args, &working_dir).await
+        }
+    }
+}
+
+/// Sources from workspace members carry the member's dir.
+fn join_working_dir(working_dir: &Path, sub_dir: Option<&str>) -> PathBuf {
+    match sub_dir {
+        Some(sub_dir) => working_dir.join(sub_dir),
+        None => working_dir.to_path_buf(),
+    }
+}
+
+async fn cmd_output(
+    program: &str,
+    arguments: &[impl AsRef<str>],
+    working_dir: &Path,
+) -> Res<String> {
+    debug!(program, "running command for env var");
+    let output = tokio::process::Command::new(program)
+        .args(arguments.iter().map(|arg| arg.as_ref()))
+        .current_dir(working_dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .wrap_err_with(|| format!("error spawning {program:?}"))?;
+    if !output.status.success() {
+        eyre::bail!(
+            "{program:?} failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|_| ferr!("{program:?} printed non utf8 output"))?;
+    Ok(stdout.trim_end_matches(['\n', '\r']).to_string())
+}
@@ -0,0 +1,213 @@
+//! Interpolation of `posix.envVar` values.
+//!
+//! Values can reference other vars of the same env using `${VAR}` and
+//! a few ghjk specific values:
+//! - `${ghjk.dataDir}`: the ghjk data dir
+//! - `${ghjk.envDir}`: the dir the env is cooked into
+//! - `${env.name}`: the name of the env, or its key if unnamed
+//!
+//! `$${` escapes to a literal `${`. References to anything else
+//! are kept verbatim.
+
+use crate::interlude::*;
+
+/// A piece of an interpolated value.
+///
+/// Paths that are under the ghjkdir or data dir are kept symbolic
+/// so that activators can refer to them through shell variables and
+/// stay relocatable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Lit(String),
+    GhjkDir,
+    DataDir,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Segment>);
+
+pub struct TemplateCtx<'a> {
+    pub ghjk_dir: &'a Path,
+    pub data_dir: &'a Path,
+    pub env_dir: &'a Path,
+    pub env_name: &'a str,
+}
+
+enum Token {
+    Lit(String),
+    Ref {
+        name: String,
+        /// The reference as written, including the braces
+        raw: String,
+    },
+}
+
+fn tokenize(key: &str, raw: &str) -> Res<Vec<Token>> {
+    let mut out = vec![];
+    let mut lit = String::new();
+    let mut rest = raw;
+    while let Some(idx) = rest.find('$') {
+        lit.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            lit.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let Some(end) = after.find('}') else {
+                eyre::bail!("unterminated reference in value of env var \"{key}\": {raw:?}");
+            };
+            if !lit.is_empty() {
+                out.push(Token::Lit(std::mem::take(&mut lit)));
+            }
+            let name = after[..end].trim();
+            if name.is_empty() {
+                eyre::bail!("empty reference in value of env var \"{key}\": {raw:?}");
+            }
+            out.push(Token::Ref {
+                name: name.to_string(),
+                raw: rest[..end + 3].to_string(),
+            });
+            rest = &after[end + 1..];
+        } else {
+            lit.push('$');
+            rest = &rest[1..];
+        }
+    }
+    lit.push_str(rest);
+    if !lit.is_empty() {
+        out.push(Token::Lit(lit));
+    }
+    Ok(out)
+}
+
+/// Expands the references in all the provided values.
+pub fn expand_all(
+    vars: &IndexMap<String, String>,
+    cx: &TemplateCtx<'_>,
+) -> Res<IndexMap<String, Template>> {
+    let mut done: IndexMap<String, Template> = IndexMap::new();
+    for key in vars.keys() {
+        expand_key(key, vars, cx, &mut done, &mut vec![])?;
+    }
+    // keep the order of the input
+    Ok(vars
+        .keys()
+        .map(|key| (key.clone(), done.swap_remove(key).unwrap_or_log()))
+        .collect())
+}
+
+fn expand_key(
+    key: &str,
+    vars: &IndexMap<String, String>,
+    cx: &TemplateCtx<'_>,
+    done: &mut IndexMap<String, Template>,
+    stack: &mut Vec<String>,
+) -> Res<Template> {
+    if let Some(tmpl) = done.get(key) {
+        return Ok(tmpl.clone());
+    }
+    if stack.iter().any(|seen| seen == key) {
+        stack.push(key.to_string());
+        eyre::bail!("cyclic env var reference: {}", stack.join(" -> "));
+    }
+    let raw = &vars[key];
+    stack.push(key.to_string());
+    let mut segments = vec![];
+    for token in tokenize(key, raw)? {
+        match token {
+            Token::Lit(lit) => segments.push(Segment::Lit(lit)),
+            Token::Ref { name, raw } => match name.as_str() {
+                "ghjk.dataDir" => segments.push(Segment::DataDir),
+                "ghjk.envDir" => segments.extend(path_segments(cx.env_dir, cx)),
+                "env.name" => segments.push(Segment::Lit(cx.env_name.to_string())),
+                other if vars.contains_key(other) => {
+                    let dep = expand_key(other, vars, cx, done, stack)?;
+                    segments.extend(dep.0);
+                }
+                // values like `${HOME}` that were written for
+                // the shell are passed through as is
+                _ => segments.push(Segment::Lit(raw)),
+            },
+        }
+    }
+    stack.pop();
+    let tmpl = Template::new(segments);
+    done.insert(key.to_string(), tmpl.clone());
+    Ok(tmpl)
+}
+
+fn path_segments(path: &Path, cx: &TemplateCtx<'_>) -> Vec<Segment> {
+    for (base, seg) in [(cx.ghjk_dir, Segment::GhjkDir), (cx.data_dir, Segment::DataDir)] {
+        if let Ok(rel) = path.strip_prefix(base) {
+            let mut out = vec![seg];
+            if !rel.as_os_str().is_empty() {
+                out.push(Segment::Lit(format!("/{}", rel.to_string_lossy())));
+            }
+            return out;
+        }
+    }
+    vec![Segment::Lit(path.to_string_lossy().to_string())]
+}
+
+impl Template {
+    fn new(segments: Vec<Segment>) -> Self {
+        // merge adjacent literals
+        let mut out: Vec<Segment> = vec![];
+        for seg in segments {
+            match (out.last_mut(), seg) {
+                (Some(Segment::Lit(prev)), Segment::Lit(next)) => prev.push_str(&next),
+                (_, seg) => out.push(seg),
+            }
+        }
+        Self(out)
+    }
+
+    /// Returns the value if it doesn't contain any symbolic segments.
+    pub fn as_literal(&self) -> Option<&str> {
+        match &self.0[..] {
+            [] => Some(""),
+            [Segment::Lit(lit)] => Some(lit),
+            _ => None,
+        }
+    }
+
+    /// Render to the final value with all paths absolutized.
+    pub fn render(&self, ghjk_dir: &str, data_dir: &str) -> String {
+        self.0
+            .iter()
+            .map(|seg| match seg {
+                Segment::Lit(lit) => &lit[..],
+                Segment::GhjkDir => ghjk_dir,
+                Segment::DataDir => data_dir,
+            })
+            .collect()
+    }
+
+    /// Render as a single posix shell word, single quoting literals and
+    /// referring to the provided shell variables for the dirs.
+    pub fn posix_word(&self, ghjk_dir_var: &str, data_dir_var: &str) -> String {
+        self.0
+            .iter()
+            .map(|seg| match seg {
+                Segment::Lit(lit) => format!("'{}'", lit.replace("'", "'\\''")),
+                Segment::GhjkDir => format!("\"${{{ghjk_dir_var}}}\""),
+                Segment::DataDir => format!("\"${{{data_dir_var}}}\""),
+            })
+            .collect()
+    }
+
+    /// Render as a single fish shell word, single quoting literals and
+    /// referring to the provided shell variables for the dirs.
+    pub fn fish_word(&self, ghjk_dir_var: &str, data_dir_var: &str) -> String {
+        self.0
+            .iter()
+            .map(|seg| match seg {
+                Segment::Lit(lit) => {
+                    format!("'{}'", lit.replace("\\", "\\\\").replace("'", "\\'"))
+                }
+                Segment::GhjkDir => format!("\"${ghjk_dir_var}\""),
+                Segment::DataDir => format!("\"${data_dir_var}\""),
+            })
+            .collect()
+    }
+}
@@ -6,31 +6,64 @@ use futures::future::BoxFuture;
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub enum WellKnownProvision {
     #[serde(rename = "posix.envVar")]
-    PosixEnvVar { key: String, val: String },
-    #[serde(rename = "hook.onEnter.posixExec")]
-    HookOnEnterPosixExec {
-        program: String,
-        arguments: Vec<String>,
+    PosixEnvVar {
+        key: String,
+        val: String,
+        /// How to merge with values set by earlier provisions of the same key
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        merge: Option<EnvVarMerge>,
     },
+    #[serde(rename = "hook.onEnter.posixExec")]
+    HookOnEnterPosixExec(PosixExecHook),
     #[serde(rename = "hook.onExit.posixExec")]
-    HookOnExitPosixExec {
-        program: String,
-        arguments: Vec<String>,
-    },
+    HookOnExitPosixExec(PosixExecHook),
     #[serde(rename = "posix.exec")]
     PosixExec {
         #[serde(rename = "absolutePath")]
         absolute_path: PathBuf,
+        /// The port install that provided the file if any
+        #[serde(rename = "instId", default, skip_serializing_if = "Option::is_none")]
+        inst_id: Option<String>,
     },
     #[serde(rename = "posix.sharedLib")]
     PosixSharedLib {
         #[serde(rename = "absolutePath")]
         absolute_path: PathBuf,
+        /// The port install that provided the file if any
+        #[serde(rename = "instId", default, skip_serializing_if = "Option::is_none")]
+        inst_id: Option<String>,
     },
     #[serde(rename = "posix.headerFile")]
     PosixHeaderFile {
         #[serde(rename = "absolutePath")]
         absolute_path: PathBuf,
+        /// The port install that provided the file if any
+        #[serde(rename = "instId", default, skip_serializing_if = "Option::is_none")]
+        inst_id: Option<String>,
+    },
+    #[serde(rename = "posix.pkgConfig")]
+    PosixPkgConfig {
+        #[serde(rename = "absolutePath")]
+        absolute_path: PathBuf,
+        /// The port install that provided the file if any
+        #[serde(rename = "instId", default, skip_serializing_if = "Option::is_none")]
+        inst_id: Option<String>,
+    },
+    #[serde(rename = "posix.manPage")]
+    PosixManPage {
+        #[serde(rename = "absolutePath")]
+        absolute_path: PathBuf,
+        /// The port install that provided the file if any
+        #[serde(rename = "instId", default, skip_serializing_if = "Option::is_none")]
+        inst_id: Option<String>,
+    },
+    #[serde(rename = "posix.shareDir")]
+    PosixShareDir {
+        #[serde(rename = "absolutePath")]
+        absolute_path: PathBuf,
+        /// The port install that provided the file if any
+        #[serde(rename = "instId", default, skip_serializing_if = "Option::is_none")]
+        inst_id: Option<String>,
     },
     #[serde(rename = "ghjk.ports.Install")]
     GhjkPortsInstall {
@@ -62,11 +95,14 @@ impl WellKnownProvision {
     pub fn provision_type(&self) -> &'static str {
         match self {
             WellKnownProvision::PosixEnvVar { .. } => "posix.envVar",
-            WellKnownProvision::HookOnEnterPosixExec { .. } => "hook.onEnter.posixExec",
-            WellKnownProvision::HookOnExitPosixExec { .. } => "hook.onExit.posixExec",
+            WellKnownProvision::HookOnEnterPosixExec(_) => "hook.onEnter.posixExec",
+            WellKnownProvision::HookOnExitPosixExec(_) => "hook.onExit.posixExec",
             WellKnownProvision::PosixExec { .. } => "posix.exec",
             WellKnownProvision::PosixSharedLib { .. } => "posix.sharedLib",
             WellKnownProvision::PosixHeaderFile { .. } => "posix.headerFile",
+            WellKnownProvision::PosixPkgConfig { .. } => "posix.pkgConfig",
+            WellKnownProvision::PosixManPage { .. } => "posix.manPage",
+            WellKnownProvision::PosixShareDir { .. } => "posix.shareDir",
             WellKnownProvision::GhjkPortsInstall { .. } => "ghjk.ports.Install",
             WellKnownProvision::GhjkShellAlias { .. } => "ghjk.shell.Alias",
             WellKnownProvision::PosixShellCompletionBash { .. } => "posix.shell.Completion.bash",
@@ -76,6 +112,66 @@ impl WellKnownProvision {
     }
 }
 
+/// Merge policy for `posix.envVar` provisions that target a key
+/// already set by an earlier provision.
+///
+/// Provisions are applied in recipe order which follows the inheritance
+/// chain, parents first. The last writer wins unless a policy says otherwise.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "strategy", rename_all = "camelCase")]
+pub enum EnvVarMerge {
+    /// Replace any previous value.
+    Override,
+    /// Put the value in front of the previous value.
+    Prepend {
+        #[serde(default = "default_env_var_separator")]
+        separator: String,
+    },
+    /// Put the value after the previous value.
+    Append {
+        #[serde(default = "default_env_var_separator")]
+        separator: String,
+    },
+    /// Fail cooking if any other provision sets the same key.
+    Error,
+}
+
+/// A command run by the activators when entering or exiting an env.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PosixExecHook {
+    pub program: String,
+    pub arguments: Vec<String>,
+    /// Seconds after which the hook is killed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Don't wait for the hook to finish
+    ///
+    /// Failures of background hooks go unreported.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub background: bool,
+    #[serde(default)]
+    pub failure_mode: HookFailureMode,
+}
+
+/// What the activators do when a hook fails.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum HookFailureMode {
+    /// Print a warning and carry on
+    #[default]
+    Warn,
+    Ignore,
+    /// Deactivate the env and return an error from the activator.
+    ///
+    /// Treated like `Warn` for onExit hooks.
+    Abort,
+}
+
+fn default_env_var_separator() -> String {
+    ":".into()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct WellKnownEnvRecipe {
@@ -104,6 +200,86 @@ pub enum Provision {
 pub struct EnvRecipe {
     pub desc: Option<String>,
     pub provides: Vec<Provision>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<EnvProvenance>,
+}
+
+/// Where the provisions of an env came from through inheritance.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EnvProvenance {
+    /// Name of the env the recipe was declared as
+    pub name: String,
+    /// The envs directly inherited from, in inheritance order
+    pub parents: Vec<EnvParentRef>,
+    /// Name of the env each item of `provides` was declared in
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EnvParentRef {
+    pub name: String,
+    pub env_key: String,
+}
+
+/// Env var provisions whose value is computed in Rust at cook time.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DynEnvVarProvision {
+    pub key: String,
+    #[serde(flatten)]
+    pub source: DynEnvVarSource,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "ty")]
+pub enum DynEnvVarSource {
+    /// The stdout of the program, trailing newlines trimmed.
+    #[serde(rename = "posix.envVarCmd")]
+    Cmd {
+        program: String,
+        #[serde(default)]
+        arguments: Vec<String>,
+        /// Dir to run the program in, relative to the ghjkfile's dir.
+        #[serde(
+            rename = "workingDir",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        working_dir: Option<String>,
+    },
+    /// The contents of the file, trailing newlines trimmed.
+    ///
+    /// Relative paths are resolved against the ghjkfile's dir.
+    #[serde(rename = "posix.envVarFile")]
+    File { path: String },
+    /// Metadata of the git repo the ghjkfile is in.
+    #[serde(rename = "posix.envVarGit")]
+    Git {
+        field: GitField,
+        /// Dir to query the repo from, relative to the ghjkfile's dir.
+        #[serde(
+            rename = "workingDir",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        working_dir: Option<String>,
+    },
+}
+
+impl DynEnvVarSource {
+    pub const TYPES: [&'static str; 3] = ["posix.envVarCmd", "posix.envVarFile", "posix.envVarGit"];
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum GitField {
+    /// Hash of the `HEAD` commit.
+    Commit,
+    /// Abbreviated hash of the `HEAD` commit.
+    ShortCommit,
+    /// Name of the checked out branch, `HEAD` if detached.
+    Branch,
 }
 
 /// A function that batch converts strange provisions of a certain kind to well known ones.
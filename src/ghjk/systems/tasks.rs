@@ -9,6 +9,13 @@ use crate::systems::{ConfigBlackboard, SystemCliCommand, SystemInstance};
 use exec::{build_task_graph, exec_task, TaskGraph};
 use types::{TaskDefHashed, TasksModuleConfig, TASK_ALIAS_PROVISION_TY};
 
+pub static LOCK_ENTRY_MIGRATIONS: crate::host::migrate::Migrations =
+    crate::host::migrate::Migrations {
+        name: "tasks lock entry",
+        current: "0",
+        steps: &[],
+    };
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TasksLockState {
     pub version: String,
@@ -93,15 +100,12 @@ impl SystemInstance for TasksSystemInstance {
     }
 
     async fn load_lock_entry(&self, raw: serde_json::Value) -> Res<Self::LockState> {
-        let entry: TasksLockState = serde_json::from_value(raw)?;
-        if entry.version != "0" {
-            eyre::bail!("unexpected version tag deserializing lockEntry");
-        }
+        let entry: TasksLockState = serde_json::from_value(LOCK_ENTRY_MIGRATIONS.migrated(raw)?)?;
         Ok(entry)
     }
 
     async fn gen_lock_entry(&self) -> Res<serde_json::Value> {
-        Ok(serde_json::json!({ "version": "0" }))
+        Ok(serde_json::json!({ "version": LOCK_ENTRY_MIGRATIONS.current }))
     }
 
     async fn commands(&self) -> Res<Vec<SystemCliCommand>> {
@@ -119,12 +123,14 @@ impl SystemInstance for TasksSystemInstance {
             // Get basic description from task definition
             let mut long_desc = match task_def {
                 TaskDefHashed::DenoFileV1(d) => d.desc.clone().unwrap_or_default(),
+                TaskDefHashed::CmdV1(d) => d.desc.clone().unwrap_or_default(),
             };
             let desc = long_desc.clone();
 
             // Add dependency information to description
             let deps = match task_def {
                 TaskDefHashed::DenoFileV1(d) => d.depends_on.as_deref().unwrap_or(&[]),
+                TaskDefHashed::CmdV1(d) => d.depends_on.as_deref().unwrap_or(&[]),
             };
             if !deps.is_empty() {
                 let deps_str = deps.join(", ");
@@ -160,52 +166,42 @@ impl SystemInstance for TasksSystemInstance {
                 task_cmd = task_cmd.hide(true);
             }
 
-            // Create action for task execution
-            let tcx = self.tcx.clone();
-            let task_key_clone = task_key.clone();
-            let scx = self.scx.clone();
-            let action: crate::systems::CliCommandAction = Box::new(move |matches| {
-                let tcx = tcx.clone();
-                let scx = scx.clone();
-                let task_key = task_key_clone.clone();
-                async move {
-                    // Extract arguments
-                    let args: Vec<String> = matches
-                        .get_many::<String>("args")
-                        .map(|v| v.cloned().collect())
-                        .unwrap_or_default();
-
-                    // Execute task
-                    let state: Arc<LoadedState> = scx.get_bb(TasksSystemInstance::BB_STATE_KEY);
-                    let _output = exec_task(
-                        &tcx.gcx,
-                        &tcx.ecx,
-                        &scx,
-                        &state.config,
-                        &state.graph,
-                        &task_key,
-                        args,
-                    )
-                    .await
-                    .wrap_err_with(|| format!("error executing task {task_key}"))?;
-
-                    Ok(())
-                }
-                .boxed()
-            });
-
+            // the tasks command's action runs the task
             task_commands.insert(
                 task_key.clone().into(),
                 SystemCliCommand {
                     name: task_key.clone().into(),
                     clap: task_cmd,
                     sub_commands: IndexMap::new(),
-                    action: Some(action),
+                    action: None,
                 },
             );
         }
         task_commands.sort_unstable_keys();
 
+        let tcx = self.tcx.clone();
+        let scx = self.scx.clone();
+        let action: crate::systems::CliCommandAction = Box::new(move |matches| {
+            let tcx = tcx.clone();
+            let scx = scx.clone();
+            async move {
+                let state: Arc<LoadedState> = scx.get_bb(TasksSystemInstance::BB_STATE_KEY);
+                if let Some(name) = matches.get_one::<String>("all-members") {
+                    return run_on_all_members(&tcx, &scx, &state, name).await;
+                }
+                let Some((task_key, matches)) = matches.subcommand() else {
+                    unreachable!("clap should require a task or --all-members");
+                };
+                // Extract arguments
+                let args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|v| v.cloned().collect())
+                    .unwrap_or_default();
+                run_task(&tcx, &scx, &state, task_key, args).await
+            }
+            .boxed()
+        });
+
         // Create main tasks command with task subcommands
         let tasks_cmd = SystemCliCommand {
             name: "tasks".into(),
@@ -213,11 +209,68 @@ impl SystemInstance for TasksSystemInstance {
                 .visible_alias("x")
                 .about("Tasks module, execute your task programs.")
                 .before_long_help("The named tasks in your ghjkfile will be listed here.")
-                .disable_help_subcommand(true),
+                .disable_help_subcommand(true)
+                .arg_required_else_help(true)
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    clap::Arg::new("all-members")
+                        .long("all-members")
+                        .value_name("TASK")
+                        .help("Run the named task in the workspace and each of its members that declare it."),
+                ),
             sub_commands: task_commands,
-            action: None,
+            action: Some(action),
         };
 
         Ok(vec![tasks_cmd])
     }
 }
+
+async fn run_task(
+    tcx: &TasksCtx,
+    scx: &crate::systems::SystemsCtx,
+    state: &LoadedState,
+    task_key: &str,
+    args: Vec<String>,
+) -> Res<()> {
+    let _output = exec_task(
+        &tcx.gcx,
+        &tcx.ecx,
+        scx,
+        &state.config,
+        &state.graph,
+        task_key,
+        args,
+    )
+    .await
+    .wrap_err_with(|| format!("error executing task {task_key}"))?;
+    Ok(())
+}
+
+/// Run the task of the given name in the workspace's ghjkfile
+/// and then in each of the members, in order, stopping at the first failure.
+async fn run_on_all_members(
+    tcx: &TasksCtx,
+    scx: &crate::systems::SystemsCtx,
+    state: &LoadedState,
+    name: &str,
+) -> Res<()> {
+    let task_keys = std::iter::once(name.to_string())
+        .chain(
+            state
+                .config
+                .members
+                .iter()
+                .map(|member| format!("{member}:{name}")),
+        )
+        .filter(|key| state.config.tasks.contains_key(key))
+        .collect::<Vec<_>>();
+    if task_keys.is_empty() {
+        eyre::bail!("no task named '{name}' found in the workspace or any of its members");
+    }
+    for task_key in task_keys {
+        info!(%task_key, "running task");
+        run_task(tcx, scx, state, &task_key, vec![]).await?;
+    }
+    Ok(())
+}
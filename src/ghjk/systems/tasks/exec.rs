@@ -1,6 +1,9 @@
-use crate::{interlude::*, systems::envs::EnvsCtx};
+use crate::{
+    interlude::*,
+    systems::envs::{hooks, EnvsCtx},
+};
 
-use super::types::{DenoWorkerTaskDefHashed, TaskDefHashed, TasksModuleConfig};
+use super::types::{TaskDefHashed, TasksModuleConfig};
 
 #[derive(Debug, Clone)]
 pub struct TaskGraph {
@@ -21,6 +24,7 @@ pub fn build_task_graph(tasks: &TasksModuleConfig) -> Res<TaskGraph> {
     for (hash, task) in &tasks.tasks {
         let depends_on = match task {
             TaskDefHashed::DenoFileV1(def) => def.depends_on.clone().unwrap_or_default(),
+            TaskDefHashed::CmdV1(def) => def.depends_on.clone().unwrap_or_default(),
         };
 
         if depends_on.is_empty() {
@@ -41,6 +45,7 @@ pub fn build_task_graph(tasks: &TasksModuleConfig) -> Res<TaskGraph> {
                     let dep_task = tasks.tasks.get(dep)?;
                     let dep_deps = match dep_task {
                         TaskDefHashed::DenoFileV1(d) => d.depends_on.as_deref().unwrap_or(&[]),
+                        TaskDefHashed::CmdV1(d) => d.depends_on.as_deref().unwrap_or(&[]),
                     };
                     if dep_deps.iter().any(|x| x == name) {
                         return Some(dep_task);
@@ -92,6 +97,7 @@ pub async fn exec_task(
             })?;
             let deps = match task_def {
                 TaskDefHashed::DenoFileV1(d) => d.depends_on.as_deref().unwrap_or(&[]),
+                TaskDefHashed::CmdV1(d) => d.depends_on.as_deref().unwrap_or(&[]),
             };
             for d in deps {
                 stack.push(d.clone());
@@ -123,26 +129,24 @@ pub async fn exec_task(
             .ok_or_else(|| ferr!("task '{task_key}' referenced but not found in tasks config"))?;
 
         // reduce and cook env for this task into a temp dir (no shell loaders)
-        let (env_key, deno_task): (&str, &DenoWorkerTaskDefHashed) = match task_def {
-            TaskDefHashed::DenoFileV1(def) => (&def.env_key, def),
+        let (env_key, task_env_hooks) = match task_def {
+            TaskDefHashed::DenoFileV1(def) => (&def.env_key, def.env_hooks),
+            TaskDefHashed::CmdV1(def) => (&def.env_key, def.env_hooks),
         };
 
         // Create a temporary directory using tempfile inside spawn_blocking
         let task_env_dir = tokio::task::spawn_blocking({
-            let task_key_for_prefix = task_key.clone();
-            move || {
-                ::tempfile::Builder::new()
-                    .prefix(&format!(
-                        "ws_ghjkTaskEnv_{}_",
-                        task_key_for_prefix.replace(['/', '\\', ':'], "_")
-                    ))
-                    .tempdir()
-            }
+            let prefix = format!(
+                "{}{}_",
+                crate::systems::envs::task_env_dir_prefix(ecx),
+                task_key.replace(['/', '\\', ':'], "_")
+            );
+            move || ::tempfile::Builder::new().prefix(&prefix).tempdir()
         })
         .await
         .wrap_err("error joining tempdir create")??;
 
-        let env_vars: IndexMap<String, String> = {
+        let (env_vars, reduced_recipe) = {
             // Cook the environment using the envs system
             crate::systems::envs::reduce_and_cook_to(
                 ecx,
@@ -155,12 +159,25 @@ pub async fn exec_task(
             .await
             .wrap_err("error cooking environment for task")?
         };
+        // marks the dir as in use for the gc
+        tokio::fs::write(
+            task_env_dir
+                .path()
+                .join(crate::systems::envs::TASK_ENV_PID_FILE),
+            std::process::id().to_string(),
+        )
+        .await
+        .wrap_err("error writing task env pid file")?;
 
         // Merge environment with current process env and PATH handling
         let mut merged_env: IndexMap<String, String> = std::env::vars().collect();
         for (k, mut v) in env_vars {
-            if k.contains("PATH") {
-                if let Ok(prev) = std::env::var(&k) {
+            let fallback = crate::systems::envs::posix::path_var_fallback(&k);
+            if k.contains("PATH") || fallback.is_some() {
+                let prev = std::env::var(&k)
+                    .ok()
+                    .or_else(|| fallback.map(|val| val.to_string()));
+                if let Some(prev) = prev {
                     let mut parts = vec![v];
                     parts.extend(prev.split(':').map(|s| s.to_string()));
                     // dedup non-empty
@@ -171,19 +188,29 @@ pub async fn exec_task(
                             out.push(p);
                         }
                     }
+                    // empty entries are significant for some vars (MANPATH)
+                    if fallback.is_some() && prev.split(':').any(|s| s.is_empty()) {
+                        out.push(String::new());
+                    }
                     v = out.join(":");
                 }
             }
             merged_env.insert(k, v);
         }
 
+        // the hooks of the task env are only run if the task asks for them
+        // and we're not already running under a hook
+        let env_hooks = if task_env_hooks == Some(true) && !hooks::hooks_skipped() {
+            hooks::EnvHooks::from_recipe(&reduced_recipe)
+        } else {
+            default()
+        };
+
+        let mut hook_pids = vec![];
         // Execute task via Deno worker
         match task_def {
             TaskDefHashed::DenoFileV1(def) => {
-                let ghjkfile = gcx
-                    .config
-                    .ghjkfile
-                    .as_ref()
+                let ghjkfile = &task_ghjkfile(gcx, def.ghjkfile.as_deref())
                     .ok_or_else(|| ferr!("denoFile task found but no ghjkfile; running on lockfile alone is unsupported for tasks"))?;
                 let working_dir = if let Some(wd) = &def.working_dir {
                     ghjkfile.parent().unwrap_or(Path::new(".")).join(wd)
@@ -209,18 +236,86 @@ pub async fn exec_task(
                     .map_err(|_| ferr!("invalid ghjkfile path for file URL"))?
                     .to_string();
 
+                hook_pids.extend(
+                    hooks::run_hooks(ecx, &env_hooks.on_enter, false, &merged_env, &working_dir)
+                        .await
+                        .wrap_err("error running onEnter hooks of task env")?,
+                );
+
                 // Call exec_task_deno to execute the task
-                let task_output = exec_task_deno(gcx, &ghjkfile_uri, &payload)
-                    .await
-                    .wrap_err("error executing deno task")?;
+                let task_output = exec_task_deno(gcx, &ghjkfile_uri, &payload).await;
+
+                // exit hooks run even if the task failed
+                hook_pids.extend(
+                    hooks::run_hooks(ecx, &env_hooks.on_exit, true, &merged_env, &working_dir)
+                        .await
+                        .wrap_err("error running onExit hooks of task env")?,
+                );
+
+                let task_output = task_output.wrap_err("error executing deno task")?;
 
                 // Store the task output
-                output.insert(deno_task.key.clone(), task_output);
+                output.insert(def.key.clone(), task_output);
+            }
+            TaskDefHashed::CmdV1(def) => {
+                let Some((program, cmd_args)) = def.cmd.split_first() else {
+                    eyre::bail!("task '{task_key}' has an empty cmd");
+                };
+                let base_dir = task_ghjkfile(gcx, def.ghjkfile.as_deref())
+                    .as_ref()
+                    .and_then(|path| path.parent())
+                    .map(|path| path.to_path_buf())
+                    .unwrap_or_else(|| PathBuf::from("."));
+                let working_dir = match &def.working_dir {
+                    Some(wd) => base_dir.join(wd),
+                    None => base_dir,
+                };
+
+                hook_pids.extend(
+                    hooks::run_hooks(ecx, &env_hooks.on_enter, false, &merged_env, &working_dir)
+                        .await
+                        .wrap_err("error running onEnter hooks of task env")?,
+                );
+
+                let status = tokio::process::Command::new(program)
+                    .args(cmd_args)
+                    .args(&args)
+                    .current_dir(&working_dir)
+                    .env_clear()
+                    .envs(&merged_env)
+                    .status()
+                    .await;
+
+                // exit hooks run even if the task failed
+                hook_pids.extend(
+                    hooks::run_hooks(ecx, &env_hooks.on_exit, true, &merged_env, &working_dir)
+                        .await
+                        .wrap_err("error running onExit hooks of task env")?,
+                );
+
+                let status = status.wrap_err_with(|| format!("error spawning {program:?}"))?;
+                if !status.success() {
+                    eyre::bail!("task '{task_key}' failed with {status}");
+                }
+                output.insert(task_key.clone(), serde_json::Value::Null);
             }
         }
 
-        // Clean up the tempdir using spawn_blocking to avoid blocking the async runtime
-        let _ = tokio::task::spawn_blocking(move || drop(task_env_dir)).await;
+        if hook_pids.is_empty() {
+            // Clean up the tempdir using spawn_blocking to avoid blocking the async runtime
+            let _ = tokio::task::spawn_blocking(move || drop(task_env_dir)).await;
+        } else {
+            // background hooks still make use of the env dir so it's
+            // left to the gc to clean up once they're done
+            tokio::fs::write(
+                task_env_dir
+                    .into_path()
+                    .join(crate::systems::envs::TASK_ENV_PID_FILE),
+                hook_pids.iter().join("\n"),
+            )
+            .await
+            .wrap_err("error writing task env pid file")?;
+        }
 
         // Mark as completed
         work_set.remove(&task_key);
@@ -259,6 +354,16 @@ pub async fn exec_task(
     Ok(output)
 }
 
+/// The ghjkfile that declared the task, tasks of workspace members
+/// record the path to theirs relative to the workspace's.
+fn task_ghjkfile(gcx: &GhjkCtx, member_ghjkfile: Option<&Path>) -> Option<PathBuf> {
+    let ghjkfile = gcx.config.ghjkfile.as_ref()?;
+    match member_ghjkfile {
+        Some(path) => Some(ghjkfile.parent()?.join(path)),
+        None => Some(ghjkfile.clone()),
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ExecTaskArgs<'a> {
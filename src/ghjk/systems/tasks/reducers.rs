@@ -19,22 +19,28 @@ pub fn task_alias_reducer(scx: Arc<crate::systems::SystemsCtx>) -> ProvisionRedu
                     scx.get_bb(super::TasksSystemInstance::BB_STATE_KEY);
                 // map local key to final visible key
                 for (task_key, task_def) in state.config.tasks.iter() {
-                    let (alias_name, desc) = match task_def {
+                    let (desc, depends_on) = match task_def {
                         crate::systems::tasks::types::TaskDefHashed::DenoFileV1(def) => {
-                            let mut description = def.desc.clone().unwrap_or_default();
-                            if let Some(deps) = def.depends_on.as_ref() {
-                                if !deps.is_empty() {
-                                    let deps_str = deps.join(", ");
-                                    if description.is_empty() {
-                                        description = format!("Depends on: {}", deps_str);
-                                    } else {
-                                        description =
-                                            format!("{}\nDepends on: {}", description, deps_str);
-                                    }
+                            (&def.desc, &def.depends_on)
+                        }
+                        crate::systems::tasks::types::TaskDefHashed::CmdV1(def) => {
+                            (&def.desc, &def.depends_on)
+                        }
+                    };
+                    let (alias_name, desc) = {
+                        let mut description = desc.clone().unwrap_or_default();
+                        if let Some(deps) = depends_on.as_ref() {
+                            if !deps.is_empty() {
+                                let deps_str = deps.join(", ");
+                                if description.is_empty() {
+                                    description = format!("Depends on: {}", deps_str);
+                                } else {
+                                    description =
+                                        format!("{}\nDepends on: {}", description, deps_str);
                                 }
                             }
-                            (task_key.clone(), description)
                         }
+                        (task_key.clone(), description)
                     };
                     x_task_exists = x_task_exists || alias_name == "x";
                     output.push(WellKnownProvision::GhjkShellAlias {
@@ -110,7 +116,11 @@ pub fn dyn_env_reducer(
                     .await
                     .wrap_err_with(|| ferr!("error executing task for env var: {key}"))?;
 
-                output.push(WellKnownProvision::PosixEnvVar { key, val });
+                output.push(WellKnownProvision::PosixEnvVar {
+                    key,
+                    val,
+                    merge: None,
+                });
             }
 
             if !bad_provisions.is_empty() {
@@ -139,12 +149,13 @@ async fn execute_task_for_env_var(
         .config
         .tasks
         .iter()
-        .find(|(_, task)| {
+        .find(|(key, task)| {
             // Extract the key from the task definition
             match task {
                 crate::systems::tasks::types::TaskDefHashed::DenoFileV1(deno_task) => {
                     deno_task.key == task_key
                 }
+                crate::systems::tasks::types::TaskDefHashed::CmdV1(_) => *key == task_key,
             }
         })
         .map(|(k, _)| k.clone())
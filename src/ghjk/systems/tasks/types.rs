@@ -15,6 +15,9 @@ pub struct TaskAliasProvision {
 pub struct TasksModuleConfig {
     pub tasks: IndexMap<String, TaskDefHashed>,
     pub tasks_named: Vec<String>,
+    /// Names of the workspace members whose tasks were merged in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,6 +25,8 @@ pub struct TasksModuleConfig {
 pub enum TaskDefHashed {
     #[serde(rename = "denoFile@v1")]
     DenoFileV1(DenoWorkerTaskDefHashed),
+    #[serde(rename = "cmd@v1")]
+    CmdV1(CmdTaskDefHashed),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,6 +35,30 @@ pub struct DenoWorkerTaskDefHashed {
     pub desc: Option<String>,
     pub working_dir: Option<String>,
     pub depends_on: Option<Vec<String>>,
+    /// Run the onEnter and onExit hooks of the task env around the task
+    pub env_hooks: Option<bool>,
     pub env_key: String,
     pub key: String,
+    /// The ghjkfile declaring the task relative to the workspace's,
+    /// only set for tasks of workspace members
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ghjkfile: Option<PathBuf>,
+}
+
+/// A task that runs a program directly, used by declarative ghjkfiles.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CmdTaskDefHashed {
+    pub desc: Option<String>,
+    pub working_dir: Option<String>,
+    pub depends_on: Option<Vec<String>>,
+    /// Run the onEnter and onExit hooks of the task env around the task
+    pub env_hooks: Option<bool>,
+    pub env_key: String,
+    /// The program and its arguments, any args passed to the task are appended
+    pub cmd: Vec<String>,
+    /// The ghjkfile declaring the task relative to the workspace's,
+    /// only set for tasks of workspace members
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ghjkfile: Option<PathBuf>,
 }
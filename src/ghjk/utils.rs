@@ -252,23 +252,32 @@ pub async fn file_exists(path: &Path) -> Result<bool, std::io::Error> {
 }
 
 pub async fn find_entry_recursive(from: &Path, name: &str) -> Res<Option<PathBuf>> {
+    find_any_entry_recursive(from, &[name]).await
+}
+
+/// Look for any of the names in the directory and its ancestors.
+///
+/// Names earlier in the list are preferred within a single directory
+/// but an entry in a closer directory always wins.
+pub async fn find_any_entry_recursive(from: &Path, names: &[&str]) -> Res<Option<PathBuf>> {
     let mut cur = from;
     loop {
-        let location = cur.join(name);
-        match tokio::fs::try_exists(&location).await {
-            Ok(true) => {
-                return Ok(Some(location));
-            }
-            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
-                return Err(err).wrap_err("error on file stat");
-            }
-            _ => {
-                let Some(next_cur) = cur.parent() else {
-                    return Ok(None);
-                };
-                cur = next_cur;
+        for name in names {
+            let location = cur.join(name);
+            match tokio::fs::try_exists(&location).await {
+                Ok(true) => {
+                    return Ok(Some(location));
+                }
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(err).wrap_err("error on file stat");
+                }
+                _ => {}
             }
         }
+        let Some(next_cur) = cur.parent() else {
+            return Ok(None);
+        };
+        cur = next_cur;
     }
 }
 
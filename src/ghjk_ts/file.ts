@@ -35,8 +35,12 @@ import type {
 } from "../sys_deno/tasks/types.ts";
 // envs
 import {
+  type BuiltinDynVarProvision,
   type EnvRecipe,
   type EnvsModuleConfig,
+  type EnvVarMerge,
+  type GitField,
+  type HookOpts,
   type Provision,
   type WellKnownProvision,
 } from "../sys_deno/envs/types.ts";
@@ -68,17 +72,25 @@ export type EnvDefArgs = {
    */
   inherit?: EnvParent;
   desc?: string;
-  vars?: Record<string, string | number>;
+  vars?: Record<string, string | number | EnvVarValue | BuiltinDynEnvValue>;
   /**
    * Task to execute when environment is activated.
    */
-  onEnter?: string | string[];
+  onEnter?: EnvHookArgs | EnvHookArgs[];
   /**
    * Task to execute when environment is deactivated.
    */
-  onExit?: string | string[];
+  onExit?: EnvHookArgs | EnvHookArgs[];
 };
 
+/**
+ * A task key or a task key along with options
+ * on how the hook is run by the activators.
+ */
+export type EnvHookArgs = string | ({ task: string } & HookOpts);
+
+type EnvHook = { taskKey: string } & HookOpts;
+
 export type TaskFnArgs = {
   $: ReturnType<typeof task$>;
   argv: string[];
@@ -104,6 +116,13 @@ export type TaskDefArgs = {
   allowedBuildDeps?: (InstallConfigFat | AllowedPortDep)[];
   installs?: InstallConfigFat | InstallConfigFat[];
   inherit?: EnvParent;
+  /**
+   * Run the `onEnter` hooks of the task's env before the task
+   * and the `onExit` hooks after it.
+   *
+   * Hooks are otherwise only run by shells activating the env.
+   */
+  envHooks?: boolean;
 };
 
 export type DenoTaskDefArgs = TaskDefArgs & {
@@ -142,12 +161,18 @@ export class Ghjkfile {
   #tasks = new Map<string, TaskDefTyped>();
   #bb = new Map<string, unknown>();
   #seenEnvs: Record<string, [EnvBuilder, EnvFinalizer]> = {};
+  /** name of the env each var provision was declared in */
+  #varProvisionSources = new WeakMap<WellKnownProvision, string>();
   #finalizedEnvs: Record<
     string,
     {
       finalized: ReturnType<EnvFinalizer>;
       installSetId?: string;
+      /** name of the env the install set was declared in */
+      installSetSource?: string;
       vars: Record<string, string>;
+      /** env var provisions in inheritance order, parents first */
+      varProvisions: WellKnownProvision[];
       envHash: string;
     }
   > = {};
@@ -280,10 +305,10 @@ export class Ghjkfile {
       env.vars(args.vars);
     }
     if (args.onEnter) {
-      env.onEnter(...args.onEnter);
+      env.onEnter(...[args.onEnter].flat());
     }
     if (args.onExit) {
-      env.onExit(...args.onExit);
+      env.onExit(...[args.onExit].flat());
     }
     return env;
   }
@@ -315,10 +340,11 @@ export class Ghjkfile {
   }
 
   toConfig(
-    { defaultEnv, defaultBaseEnv }: {
+    { defaultEnv, defaultBaseEnv, members }: {
       defaultEnv: string;
       defaultBaseEnv: string;
       ghjkfileUrl: string;
+      members?: string[];
     },
   ) {
     // make sure referenced envs exist
@@ -365,6 +391,7 @@ export class Ghjkfile {
           id: "envs",
           config: envsConfig,
         }],
+        ...members?.length ? { members } : {},
       };
       return config;
     } catch (cause) {
@@ -393,17 +420,36 @@ export class Ghjkfile {
 
   #mergeEnvs(keys: string[], childName: string) {
     const mergedVars = {} as Record<string, [string, string] | undefined>;
+    const mergedVarProvisions = new Map<string, WellKnownProvision>();
     let mergedInstalls = new Set<string>();
-    const mergedOnEnterHooks = [];
-    const mergedOnExitHooks = [];
+    const mergedOnEnterHooks = [] as (readonly [EnvHook, string])[];
+    const mergedOnExitHooks = [] as (readonly [EnvHook, string])[];
     const mergedAllowedBuildDeps = {} as Record<
       string,
       [string, string] | undefined
     >;
     for (const parentName of keys) {
-      const { vars, installSetId, finalized } = this.#finalizedEnvs[parentName];
-      mergedOnEnterHooks.push(...finalized.onEnterHookTasks);
-      mergedOnExitHooks.push(...finalized.onExitHookTasks);
+      const { vars, varProvisions, installSetId, finalized } =
+        this.#finalizedEnvs[parentName];
+      const parentSource = finalized.name ?? parentName;
+      // parents that share ancestors will have identical
+      // provisions which we only want to apply once
+      for (const prov of varProvisions) {
+        const hash = objectHashSafe(prov);
+        if (!mergedVarProvisions.has(hash)) {
+          mergedVarProvisions.set(hash, prov);
+        }
+      }
+      mergedOnEnterHooks.push(
+        ...finalized.onEnterHookTasks.map((hook) =>
+          [hook, parentSource] as const
+        ),
+      );
+      mergedOnExitHooks.push(
+        ...finalized.onExitHookTasks.map((hook) =>
+          [hook, parentSource] as const
+        ),
+      );
       for (const [key, val] of Object.entries(vars)) {
         const conflict = mergedVars[key];
         // if parents share a parent themselves, they will have
@@ -460,6 +506,7 @@ export class Ghjkfile {
       onEnterHookTasks: mergedOnEnterHooks,
       onExitHookTasks: mergedOnExitHooks,
       vars: outVars,
+      varProvisions: [...mergedVarProvisions.values()],
     };
   }
 
@@ -564,14 +611,39 @@ export class Ghjkfile {
       const final = all[item];
 
       const base = this.#mergeEnvs(final.envBaseResolved ?? [], final.key);
+      const source = final.name ?? final.key;
       // $.dbg("processing", { parents: final.envBaseResolved, child: final.key, base });
 
       const finalVars = {
         ...base.vars,
         ...final.vars,
       };
+      // env var provisions are emitted in inheritance order
+      // and merged during cooking. Parent values that are
+      // simply overriden are dropped here. Parent values with
+      // their own policy are kept for it to be enforced.
+      const finalVarProvisions = [
+        ...base.varProvisions.filter((prov) =>
+          prov.ty != "posix.envVar" ||
+          !(prov.key in final.vars) ||
+          (final.varMerges[prov.key] &&
+            final.varMerges[prov.key].strategy != "override") ||
+          (prov.merge && prov.merge.strategy != "override")
+        ),
+        ...Object.entries(final.vars).map(([key, val]) => {
+          const prov: WellKnownProvision = {
+            ty: "posix.envVar",
+            key,
+            val,
+            ...(final.varMerges[key] ? { merge: final.varMerges[key] } : {}),
+          };
+          this.#varProvisionSources.set(prov, source);
+          return prov;
+        }),
+      ];
 
       let finalInstallSetId: string | undefined;
+      let finalInstallSetSource: string | undefined;
       {
         const installSet = this.#installSets.get(final.installSetId);
         if (installSet) {
@@ -587,35 +659,42 @@ export class Ghjkfile {
             }
           }
           finalInstallSetId = final.installSetId;
+          finalInstallSetSource = source;
         } // if there's no install set found under the id
         else {
           // implies that the env has not ports explicitly configured
           if (final.envBaseResolved) {
             // has a singluar parent
             if (final.envBaseResolved.length == 1) {
-              finalInstallSetId =
-                this.#finalizedEnvs[final.envBaseResolved[0]].installSetId;
+              const parent = this.#finalizedEnvs[final.envBaseResolved[0]];
+              finalInstallSetId = parent.installSetId;
+              finalInstallSetSource = parent.installSetSource;
             } else {
               this.#installSets.set(final.installSetId, base.installSet);
               finalInstallSetId = final.installSetId;
+              finalInstallSetSource = source;
             }
           }
         }
       }
+      const hookSources = [] as string[];
       const hooks = [
         ...base.onEnterHookTasks.map(
-          (key) => [key, "hook.onEnter.ghjkTask"] as const,
+          ([hook, hookSource]) =>
+            [hook, "hook.onEnter.ghjkTask", hookSource] as const,
         ),
         ...final.onEnterHookTasks.map(
-          (key) => [key, "hook.onEnter.ghjkTask"] as const,
+          (hook) => [hook, "hook.onEnter.ghjkTask", source] as const,
         ),
         ...base.onExitHookTasks.map(
-          (key) => [key, "hook.onExit.ghjkTask"] as const,
+          ([hook, hookSource]) =>
+            [hook, "hook.onExit.ghjkTask", hookSource] as const,
         ),
         ...final.onExitHookTasks.map(
-          (key) => [key, "hook.onExit.ghjkTask"] as const,
+          (hook) => [hook, "hook.onExit.ghjkTask", source] as const,
         ),
-      ].map(([taskKey, ty]) => {
+      ].map(([{ taskKey, ...opts }, ty, hookSource]) => {
+        hookSources.push(hookSource);
         const task = this.#tasks.get(taskKey);
         if (!task) {
           throw new Error("unable to find task for onEnterHook", {
@@ -629,6 +708,7 @@ export class Ghjkfile {
           const prov: InlineTaskHookProvision = {
             ty,
             taskKey,
+            ...opts,
           };
           return prov;
         }
@@ -647,12 +727,7 @@ export class Ghjkfile {
       const recipe: EnvRecipe = {
         desc: final.desc,
         provides: [
-          ...Object.entries(finalVars).map((
-            [key, val],
-          ) => {
-            const prov: WellKnownProvision = { ty: "posix.envVar", key, val };
-            return prov;
-          }),
+          ...finalVarProvisions,
           ...Object.entries(final.dynVars).map((
             [key, val],
           ) => {
@@ -662,6 +737,7 @@ export class Ghjkfile {
               prov,
             );
           }),
+          ...Object.values(final.builtinDynVars),
           // env hooks
           ...hooks,
           // task aliases - expanded later via reducer using state
@@ -683,12 +759,41 @@ export class Ghjkfile {
       const envHash = objectHashSafe(recipe);
       this.#finalizedEnvs[final.key] = {
         installSetId: finalInstallSetId,
+        installSetSource: finalInstallSetSource,
         vars: finalVars,
+        varProvisions: finalVarProvisions,
         finalized: final,
         envHash,
       };
-      // hashing takes care of deduplication
-      moduleConfig.envs[envHash] = recipe;
+      // provenance is added after hashing as envs that only differ
+      // in where their provisions came from are still the same env.
+      // the first env processed gets to keep its provenance
+      if (!moduleConfig.envs[envHash]) {
+        recipe.provenance = {
+          name: source,
+          parents: (final.envBaseResolved ?? []).map((parentKey) => {
+            const parent = this.#finalizedEnvs[parentKey];
+            return {
+              name: parent.finalized.name ?? parentKey,
+              envKey: parent.envHash,
+            };
+          }),
+          sources: [
+            ...finalVarProvisions.map((prov) =>
+              this.#varProvisionSources.get(prov) ?? source
+            ),
+            ...Object.keys(final.dynVars).map(() => source),
+            ...Object.keys(final.builtinDynVars).map(() => source),
+            ...hookSources,
+            // task aliases
+            source,
+            // CLI completions
+            source,
+            ...(finalInstallSetId ? [finalInstallSetSource ?? source] : []),
+          ],
+        };
+        moduleConfig.envs[envHash] = recipe;
+      }
 
       if (final.name) {
         moduleConfig.envsNamed[final.name] = envHash;
@@ -761,7 +866,7 @@ export class Ghjkfile {
     while (workingSet.length > 0) {
       const key = workingSet.pop()!;
       const args = this.#tasks.get(key)!;
-      const { workingDir, desc, dependsOn } = args;
+      const { workingDir, desc, dependsOn, envHooks } = args;
 
       const envKey = taskToEnvMap[key];
       const { envHash } = this.#finalizedEnvs[envKey];
@@ -781,6 +886,7 @@ export class Ghjkfile {
               ),
           }
           : {},
+        ...envHooks ? { envHooks } : {},
         envKey: envHash,
       };
       const taskHash = objectHash(def);
@@ -846,14 +952,16 @@ export class Ghjkfile {
             prov.ty == "hook.onEnter.ghjkTask" ||
             prov.ty == "hook.onExit.ghjkTask"
           ) {
-            const inlineProv = prov as InlineTaskHookProvision;
-            const taskKey = localToFinalKey[inlineProv.taskKey];
+            const { taskKey: localKey, ty: _ty, ...opts } =
+              prov as InlineTaskHookProvision;
+            const taskKey = localToFinalKey[localKey];
             const out: WellKnownProvision = {
               ty: /onEnter/.test(prov.ty)
                 ? "hook.onEnter.posixExec"
                 : "hook.onExit.posixExec",
               program: "ghjk",
               arguments: ["x", taskKey],
+              ...opts,
             };
             return out;
           }
@@ -899,16 +1007,39 @@ type EnvFinalizer = () => {
   installSetId: string;
   inherit: string | string[] | boolean;
   vars: Record<string, string>;
+  varMerges: Record<string, EnvVarMerge>;
   dynVars: Record<string, string>;
+  builtinDynVars: Record<string, BuiltinDynVarProvision>;
   desc?: string;
-  onEnterHookTasks: string[];
-  onExitHookTasks: string[];
+  onEnterHookTasks: EnvHook[];
+  onExitHookTasks: EnvHook[];
 };
 
 export type EnvDefArgsPartial =
   & { name?: string }
   & Omit<EnvDefArgs, "name">;
 
+/**
+ * An env var value along with the policy used to merge it
+ * with values set for the same key by parent envs.
+ */
+export type EnvVarValue = {
+  val: string | number;
+  merge: EnvVarMerge;
+};
+
+/**
+ * An env var value computed when the env is cooked without
+ * running a task.
+ * - `cmd`: the output of the program along with its arguments.
+ * - `file`: the contents of the file, relative to the ghjkfile.
+ * - `git`: metadata on the `HEAD` of the ghjkfile's repo.
+ */
+export type BuiltinDynEnvValue =
+  | { cmd: [string, ...string[]] }
+  | { file: string }
+  | { git: GitField };
+
 export type DynEnvValue =
   | (() => string | number)
   | (($_: typeof $) => string | number)
@@ -958,10 +1089,12 @@ export class EnvBuilder {
   #file: Ghjkfile;
   #inherit: string | string[] | boolean = true;
   #vars: Record<string, string | number> = {};
+  #varMerges: Record<string, EnvVarMerge> = {};
   #dynVars: Record<string, string> = {};
+  #builtinDynVars: Record<string, BuiltinDynVarProvision> = {};
   #desc?: string;
-  #onEnterHookTasks: string[] = [];
-  #onExitHookTasks: string[] = [];
+  #onEnterHookTasks: EnvHook[] = [];
+  #onExitHookTasks: EnvHook[] = [];
 
   constructor(
     file: Ghjkfile,
@@ -979,7 +1112,9 @@ export class EnvBuilder {
       vars: Object.fromEntries(
         Object.entries(this.#vars).map(([key, val]) => [key, val.toString()]),
       ),
+      varMerges: this.#varMerges,
       dynVars: this.#dynVars,
+      builtinDynVars: this.#builtinDynVars,
       desc: this.#desc,
       onExitHookTasks: this.#onExitHookTasks,
       onEnterHookTasks: this.#onEnterHookTasks,
@@ -1013,7 +1148,10 @@ export class EnvBuilder {
   /**
    * Add an environment variable.
    */
-  var(key: string, val: string | DynEnvValue) {
+  var(
+    key: string,
+    val: string | EnvVarValue | BuiltinDynEnvValue | DynEnvValue,
+  ) {
     this.vars({ [key]: val });
     return this;
   }
@@ -1021,14 +1159,48 @@ export class EnvBuilder {
   /**
    * Add multiple environment variable.
    */
-  vars(envVars: Record<string, string | number | DynEnvValue>) {
-    const vars = {}, dynVars = {};
+  vars(
+    envVars: Record<
+      string,
+      string | number | EnvVarValue | BuiltinDynEnvValue | DynEnvValue
+    >,
+  ) {
+    const vars = {}, dynVars = {}, varMerges = {}, builtinDynVars = {};
     for (const [k, v] of Object.entries(envVars)) {
       switch (typeof v) {
         case "string":
         case "number":
           Object.assign(vars, { [k]: v });
+          delete this.#varMerges[k];
           break;
+        case "object": {
+          if (!("val" in v)) {
+            const prov = "cmd" in v
+              ? {
+                ty: "posix.envVarCmd",
+                key: k,
+                program: v.cmd[0],
+                arguments: v.cmd.slice(1),
+              }
+              : "file" in v
+              ? { ty: "posix.envVarFile", key: k, path: v.file }
+              : { ty: "posix.envVarGit", key: k, field: v.git };
+            Object.assign(builtinDynVars, {
+              [k]: unwrapZodRes(
+                envsValidators.builtinDynVarProvision.safeParse(prov),
+                prov,
+              ),
+            });
+            break;
+          }
+          const merge = unwrapZodRes(
+            envsValidators.envVarMerge.safeParse(v.merge),
+            { key: k, merge: v.merge },
+          );
+          Object.assign(vars, { [k]: v.val });
+          Object.assign(varMerges, { [k]: merge });
+          break;
+        }
         case "function": {
           const taskKey = this.#file.addTask({
             ty: "denoFile@v1",
@@ -1049,10 +1221,18 @@ export class EnvBuilder {
       this.#vars,
       unwrapZodRes(validators.envVars.safeParse(vars), { envVars: vars }),
     );
+    Object.assign(
+      this.#varMerges,
+      varMerges,
+    );
     Object.assign(
       this.#dynVars,
       dynVars,
     );
+    Object.assign(
+      this.#builtinDynVars,
+      builtinDynVars,
+    );
     return this;
   }
 
@@ -1066,17 +1246,23 @@ export class EnvBuilder {
 
   /**
    * Tasks to execute on enter.
+   *
+   * Pass an object to set a timeout, run the task in the
+   * background or control what happens when it fails.
    */
-  onEnter(...taskKey: string[]) {
-    this.#onEnterHookTasks.push(...taskKey);
+  onEnter(...hooks: EnvHookArgs[]) {
+    this.#onEnterHookTasks.push(...hooks.map(normalizeEnvHook));
     return this;
   }
 
   /**
-   * Tasks to execute on enter.
+   * Tasks to execute on exit.
+   *
+   * Pass an object to set a timeout, run the task in the
+   * background or control what happens when it fails.
    */
-  onExit(...taskKey: string[]) {
-    this.#onExitHookTasks.push(...taskKey);
+  onExit(...hooks: EnvHookArgs[]) {
+    this.#onExitHookTasks.push(...hooks.map(normalizeEnvHook));
     return this;
   }
 }
@@ -1117,11 +1303,22 @@ export function task$(
   return custom$;
 }
 
-type InlineTaskHookProvision = Provision & {
+type InlineTaskHookProvision = Provision & HookOpts & {
   ty: "hook.onExit.ghjkTask" | "hook.onEnter.ghjkTask";
   taskKey: string;
 };
 
+function normalizeEnvHook(hook: EnvHookArgs): EnvHook {
+  if (typeof hook == "string") {
+    return { taskKey: hook };
+  }
+  const { task, ...opts } = hook;
+  return {
+    taskKey: task,
+    ...unwrapZodRes(envsValidators.hookOpts.safeParse(opts), { hook }),
+  };
+}
+
 function objectHashSafe(obj: unknown) {
   return objectHash(JSON.parse(JSON.stringify(obj)));
 }
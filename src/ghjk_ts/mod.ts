@@ -16,7 +16,13 @@ import { EnvBuilder, Ghjkfile, stdDeps } from "./file.ts";
 import type { DenoTaskDefArgs, EnvDefArgs, TaskFn } from "./file.ts";
 import type { ExecTaskArgs } from "../sys_deno/tasks/types.ts";
 
-export type { DenoTaskDefArgs, EnvDefArgs, TaskFn } from "./file.ts";
+export type {
+  BuiltinDynEnvValue,
+  DenoTaskDefArgs,
+  EnvDefArgs,
+  EnvVarValue,
+  TaskFn,
+} from "./file.ts";
 export { $, logger, stdDeps };
 
 export type AddEnv = {
@@ -96,6 +102,14 @@ export type FileArgs = {
    * Different envs available to the CLI.
    */
   envs?: EnvDefArgs[];
+  /**
+   * Directories, relative to this file, holding the ghjkfiles of
+   * workspace members. Globs like `packages/*` are supported.
+   *
+   * The tasks and envs of members are available as
+   * `member:task` and `member/env` respectively.
+   */
+  members?: string[];
 };
 
 type SecureConfigArgs = Omit<
@@ -155,6 +169,7 @@ export const sophon = Object.freeze({
         defaultEnv: args?.defaultEnv ?? DEFAULT_BASE_ENV_NAME,
         defaultBaseEnv: args?.defaultBaseEnv ??
           DEFAULT_BASE_ENV_NAME,
+        members: args?.members,
       });
     },
   ),
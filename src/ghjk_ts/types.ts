@@ -5,6 +5,7 @@ const serializedConfig = zod.object(
   {
     modules: zod.array(moduleValidators.moduleManifest),
     blackboard: moduleValidators.blackboard,
+    members: zod.string().array().optional(),
   },
 );
 
@@ -43,7 +43,7 @@ function ghjk_hook --on-variable PWD
         # look for ghjk dirs in pwd and parents
         set --local cur_dir $PWD
         while true 
-            if test -d $cur_dir/.ghjk; or test -d $cur_dir/ghjk.ts
+            if test -d $cur_dir/.ghjk; or test -e $cur_dir/ghjk.ts; or test -e $cur_dir/ghjk.toml; or test -e $cur_dir/ghjk.json
                 set local_ghjk_dir $cur_dir/.ghjk
                 break
             end
@@ -71,17 +71,26 @@ function ghjk_hook --on-variable PWD
             set --global --export GHJK_LAST_ENV_DIR $next_env_dir
             set --global --export GHJK_LAST_ENV_DIR_MTIME (__ghjk_get_mtime_ts $next_env_dir/activate.fish)
 
-            # FIXME: older versions of fish don't recognize -ot
-            # those in debian for example
-            # FIXME: this assumes ghjkfile is of kind ghjk.ts
-            if test (__ghjk_get_mtime_ts $next_env_dir/activate.fish) -lt (__ghjk_get_mtime_ts $local_ghjk_dir/../ghjk.ts)
-                set_color FF4500
-                if test $next_env = "default"
-                    echo "[ghjk] Possible drift from default environment, please sync..." >&2
-                else
-                    echo "[ghjk] Possible drift from active environment ($next_env), please sync..." >&2
-                end
-                set_color normal
+            # compares the env against the digests of everything
+            # that went into serializing the ghjkfile
+            set --local env_status (ghjk envs status --auto-sync $next_env 2>/dev/null)
+            switch "$env_status"
+                case stale
+                    set_color FF4500
+                    if test $next_env = "default"
+                        echo "[ghjk] Possible drift from default environment, please sync..." >&2
+                    else
+                        echo "[ghjk] Possible drift from active environment ($next_env), please sync..." >&2
+                    end
+                    set_color normal
+                case syncing
+                    set_color FF4500
+                    echo "[ghjk] Drift from environment, re-cooking in the background..." >&2
+                    set_color normal
+                case synced
+                    set_color FF4500
+                    echo "[ghjk] Drift from environment, re-cooked" >&2
+                    set_color normal
             end
         else
             set_color FF4500
@@ -42,7 +42,7 @@ ghjk_hook() {
         # use do while format to allow detection of .ghjk in root dirs
         cur_dir=$PWD
         while true; do
-            if [ -d "$cur_dir/.ghjk" ] || [ -e "$cur_dir/ghjk.ts" ]; then
+            if [ -d "$cur_dir/.ghjk" ] || [ -e "$cur_dir/ghjk.ts" ] || [ -e "$cur_dir/ghjk.toml" ] || [ -e "$cur_dir/ghjk.json" ]; then
                 local_ghjk_dir="$cur_dir/.ghjk"
                 break
             fi
@@ -72,15 +72,23 @@ ghjk_hook() {
             export GHJK_LAST_ENV_DIR
             export GHJK_LAST_ENV_DIR_MTIME
 
-            # FIXME: this assumes ghjkfile is of kind ghjk.ts
-            if [ "$(__ghjk_get_mtime_ts "$local_ghjk_dir/../ghjk.ts")" -gt "$(__ghjk_get_mtime_ts "$next_env_dir/activate.sh")" ]; then
-                if [ "$next_env" = "default" ]; then
-                    printf "\033[0;33m[ghjk] Possible drift from environment, please re-cook...\033[0m\n" >&2
-                else
-                    printf "\033[0;33m[ghjk] Possible drift from active environment (%s), please re-cook...\033[0m\n" "$next_env" >&2
-                fi
-
-            fi
+            # compares the env against the digests of everything
+            # that went into serializing the ghjkfile
+            case "$(ghjk envs status --auto-sync "$next_env" 2>/dev/null)" in
+                "stale")
+                    if [ "$next_env" = "default" ]; then
+                        printf "\033[0;33m[ghjk] Possible drift from environment, please re-cook...\033[0m\n" >&2
+                    else
+                        printf "\033[0;33m[ghjk] Possible drift from active environment (%s), please re-cook...\033[0m\n" "$next_env" >&2
+                    fi
+                ;;
+                "syncing")
+                    printf "\033[0;33m[ghjk] Drift from environment, re-cooking in the background...\033[0m\n" >&2
+                ;;
+                "synced")
+                    printf "\033[0;33m[ghjk] Drift from environment, re-cooked\033[0m\n" >&2
+                ;;
+            esac
         else
             if [ "$next_env" = "default" ]; then
                 printf "\033[0;31m[ghjk] Environment not found, please cook...\033[0m\n" >&2
@@ -10,6 +10,9 @@ const posixFileProvisionTypes = [
   "posix.exec",
   "posix.sharedLib",
   "posix.headerFile",
+  "posix.pkgConfig",
+  "posix.manPage",
+  "posix.shareDir",
 ] as const;
 
 export const hookProvisionTypes = [
@@ -28,6 +31,12 @@ export const shellAliasProvisionTypes = [
 
 export const envVarDynTy = "posix.envVarDyn";
 
+// dynamic env vars whose values are computed by the
+// rust side without running a task
+export const envVarCmdTy = "posix.envVarCmd";
+export const envVarFileTy = "posix.envVarFile";
+export const envVarGitTy = "posix.envVarGit";
+
 // we separate the posix file types in a separate
 // array in the interest of type inference
 export const wellKnownProvisionTypes = [
@@ -38,6 +47,30 @@ export const wellKnownProvisionTypes = [
   ...shellAliasProvisionTypes,
 ] as const;
 
+const envVarMerge = zod.discriminatedUnion("strategy", [
+  zod.object({ strategy: zod.literal("override") }),
+  zod.object({
+    strategy: zod.literal("prepend"),
+    separator: zod.string().optional(),
+  }),
+  zod.object({
+    strategy: zod.literal("append"),
+    separator: zod.string().optional(),
+  }),
+  zod.object({ strategy: zod.literal("error") }),
+]);
+
+const hookFailureMode = zod.enum(["warn", "ignore", "abort"]);
+
+const hookOpts = zod.object({
+  /** seconds after which the hook is killed */
+  timeout: zod.number().int().positive().optional(),
+  /** don't wait for the hook to finish, failures go unreported */
+  background: zod.boolean().optional(),
+  /** what to do when the hook fails, `abort` only applies to onEnter hooks */
+  failureMode: hookFailureMode.optional(),
+});
+
 const wellKnownProvision = zod.discriminatedUnion(
   "ty",
   [
@@ -45,16 +78,22 @@ const wellKnownProvision = zod.discriminatedUnion(
       ty: zod.literal(wellKnownProvisionTypes[0]),
       key: moduleValidators.envVarName,
       val: zod.string(),
+      merge: envVarMerge.optional(),
     }),
     ...hookProvisionTypes.map((ty) =>
       zod.object({
         ty: zod.literal(ty),
         program: zod.string(),
         arguments: zod.string().array(),
-      })
+      }).merge(hookOpts)
     ),
     ...posixFileProvisionTypes.map((ty) =>
-      zod.object({ ty: zod.literal(ty), absolutePath })
+      zod.object({
+        ty: zod.literal(ty),
+        absolutePath,
+        // the port install that provided the file
+        instId: zod.string().optional(),
+      })
     ),
     ...installProvisionTypes.map(
       (ty) =>
@@ -78,9 +117,22 @@ const wellKnownProvision = zod.discriminatedUnion(
   ],
 );
 
+const envProvenance = zod.object({
+  /** name of the env the recipe was declared as */
+  name: zod.string(),
+  /** the envs directly inherited from, in inheritance order */
+  parents: zod.object({
+    name: zod.string(),
+    envKey: zod.string(),
+  }).array(),
+  /** name of the env each item of `provides` was declared in */
+  sources: zod.string().array(),
+});
+
 const envRecipe = zod.object({
   desc: zod.string().nullish(),
   provides: zod.array(provision),
+  provenance: envProvenance.nullish(),
 });
 
 const wellKnownEnvRecipe = envRecipe.merge(zod.object({
@@ -102,10 +154,48 @@ const envVarDynProvision = zod.object({
   taskKey: zod.string(),
 });
 
+const envVarCmdProvision = zod.object({
+  ty: zod.literal(envVarCmdTy),
+  key: moduleValidators.envVarName,
+  program: zod.string(),
+  arguments: zod.string().array(),
+  /** relative to the ghjkfile's dir, set for workspace members */
+  workingDir: zod.string().optional(),
+});
+
+const envVarFileProvision = zod.object({
+  ty: zod.literal(envVarFileTy),
+  key: moduleValidators.envVarName,
+  /** relative paths are resolved against the ghjkfile's dir */
+  path: zod.string(),
+});
+
+const gitField = zod.enum(["commit", "shortCommit", "branch"]);
+
+const envVarGitProvision = zod.object({
+  ty: zod.literal(envVarGitTy),
+  key: moduleValidators.envVarName,
+  field: gitField,
+  /** relative to the ghjkfile's dir, set for workspace members */
+  workingDir: zod.string().optional(),
+});
+
+const builtinDynVarProvision = zod.discriminatedUnion("ty", [
+  envVarCmdProvision,
+  envVarFileProvision,
+  envVarGitProvision,
+]);
+
 const validators = {
+  envVarMerge,
+  hookFailureMode,
+  hookOpts,
   provision,
   wellKnownProvision,
   envVarDynProvision,
+  gitField,
+  builtinDynVarProvision,
+  envProvenance,
   envRecipe,
   envsModuleConfig,
   wellKnownEnvRecipe,
@@ -114,12 +204,21 @@ export default validators;
 
 export type EnvsModuleConfig = zod.infer<typeof validators.envsModuleConfig>;
 
+export type EnvVarMerge = zod.infer<typeof validators.envVarMerge>;
+export type HookFailureMode = zod.infer<typeof validators.hookFailureMode>;
+export type HookOpts = zod.infer<typeof validators.hookOpts>;
+export type GitField = zod.infer<typeof validators.gitField>;
+export type BuiltinDynVarProvision = zod.infer<
+  typeof validators.builtinDynVarProvision
+>;
+
 export type Provision = zod.infer<typeof validators.provision>;
 export type WellKnownProvision = zod.infer<
   typeof validators.wellKnownProvision
 >;
 
 export type EnvRecipe = zod.infer<typeof validators.envRecipe>;
+export type EnvProvenance = zod.infer<typeof validators.envProvenance>;
 
 export type WellKnownEnvRecipe = zod.infer<
   typeof validators.wellKnownEnvRecipe
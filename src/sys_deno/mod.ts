@@ -1,5 +1,5 @@
 import type { CliCommand } from "./types.ts";
-import type { Json } from "../deno_utils/mod.ts";
+import type { Json, JsonObject } from "../deno_utils/mod.ts";
 import type { Blackboard, GhjkCtx, ModuleManifest } from "./types.ts";
 
 export abstract class ModuleBase<LockEnt> {
@@ -19,3 +19,43 @@ export abstract class ModuleBase<LockEnt> {
   abstract genLockEntry(): Promise<Json> | Json;
   abstract commands(): CliCommand[];
 }
+
+/**
+ * Upgrades a lock entry from the `from` version to the `to` version.
+ */
+export type LockEntryMigration = {
+  from: string;
+  to: string;
+  apply: (raw: JsonObject) => JsonObject;
+};
+
+/**
+ * Apply the steps until the lock entry is at the `current` version.
+ * Mirrors the migration registries of the rust systems.
+ */
+export function migrateLockEntry(
+  raw: Json,
+  current: string,
+  steps: LockEntryMigration[],
+): Json {
+  let entry = raw as JsonObject;
+  for (let applied = 0;; applied += 1) {
+    const version = entry?.version;
+    if (typeof version != "string") {
+      throw new Error("lock entry has no version tag", { cause: raw });
+    }
+    if (version == current) {
+      return entry;
+    }
+    const step = steps.find((step) => step.from == version);
+    if (!step) {
+      throw new Error(
+        `unsupported lock entry version "${version}", expected "${current}" or older`,
+      );
+    }
+    if (applied == steps.length) {
+      throw new Error("cyclic lock entry migrations");
+    }
+    entry = { ...step.apply(entry), version: step.to };
+  }
+}
@@ -46,6 +46,48 @@ export abstract class PortBase {
     ];
   }
 
+  /// Paths to all the pkg-config `.pc` files provided by an install.
+  /// Glob paths will be expanded
+  listPkgConfigPaths(
+    args: ListBinPathsArgs,
+  ): Promise<string[]> | string[] {
+    return [
+      std_path.joinGlobs([
+        std_path.resolve(args.installPath, "lib", "pkgconfig"),
+        "*.pc",
+      ]),
+      std_path.joinGlobs([
+        std_path.resolve(args.installPath, "share", "pkgconfig"),
+        "*.pc",
+      ]),
+    ];
+  }
+
+  /// Paths to all the man pages provided by an install.
+  /// Glob paths will be expanded
+  listManPaths(
+    args: ListBinPathsArgs,
+  ): Promise<string[]> | string[] {
+    return [
+      std_path.joinGlobs([
+        std_path.resolve(args.installPath, "share", "man"),
+        "man*",
+        "*",
+      ]),
+    ];
+  }
+
+  /// Paths to data dirs to expose through `$XDG_DATA_DIRS`.
+  /// Glob paths will be expanded
+  ///
+  /// Empty by default since the contents of `share` tend to
+  /// overlap between installs (`share/doc` and the like).
+  listSharePaths(
+    _args: ListBinPathsArgs,
+  ): Promise<string[]> | string[] {
+    return [];
+  }
+
   /// The latest version of a port to be used when no version
   /// is specified by a user.
   /// Will default to using the last itemr returned by [`listAll`]
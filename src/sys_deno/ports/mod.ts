@@ -11,7 +11,11 @@ import validators, {
 } from "./types.ts";
 import type { InstallSet, PortsModuleConfig } from "./types.ts";
 import { type ModuleManifest } from "../types.ts";
-import { ModuleBase } from "../mod.ts";
+import {
+  type LockEntryMigration,
+  migrateLockEntry,
+  ModuleBase,
+} from "../mod.ts";
 import {
   buildInstallGraph,
   getResolutionMemo,
@@ -22,6 +26,7 @@ import { getProvisionReducerStore } from "../envs/reducer.ts";
 import { installSetReducer, installSetRefReducer } from "./reducers.ts";
 import type { Provision, ProvisionReducer } from "../envs/types.ts";
 import { getPortsCtx } from "./inter.ts";
+import * as std_ports from "./std.ts";
 import type { CliCommand } from "../types.ts";
 
 export type PortsCtx = {
@@ -37,8 +42,11 @@ const lockValidator = zod.object({
 });
 type PortsLockEnt = zod.infer<typeof lockValidator>;
 
+const lockEntryVersion = "0";
+const lockEntryMigrations: LockEntryMigration[] = [];
+
 export class PortsModule extends ModuleBase<PortsLockEnt> {
-  loadConfig(
+  async loadConfig(
     manifest: ModuleManifest,
     bb: Blackboard,
     _lockEnt: PortsLockEnt | undefined,
@@ -62,9 +70,14 @@ export class PortsModule extends ModuleBase<PortsLockEnt> {
       // install sets in the config use hash references to dedupe InstallConfigs,
       // AllowedDepSets and AllowedDeps
       // reify the references from the blackboard before continuing
-      const installs = hashedSet.installs.map((hash) =>
-        unwrapParseCurry(validators.installConfigFat.safeParse(bb[hash]))
-      );
+      const installs = [];
+      for (const hash of hashedSet.installs) {
+        installs.push(unwrapParseCurry(
+          validators.installConfigFat.safeParse(
+            await reifyPortModule(bb[hash]),
+          ),
+        ));
+      }
       const allowedDepSetHashed = unwrapParseCurry(
         validators.allowDepSetHashed.safeParse(
           bb[hashedSet.allowedBuildDeps],
@@ -80,7 +93,9 @@ export class PortsModule extends ModuleBase<PortsLockEnt> {
       );
       const set: InstallSet = {
         installs,
-        allowedBuildDeps,
+        allowedBuildDeps: hashedSet.stdDeps
+          ? { ...std_ports.map, ...allowedBuildDeps }
+          : allowedBuildDeps,
       };
       pcx.config.sets[id] = set;
     }
@@ -128,11 +143,9 @@ export class PortsModule extends ModuleBase<PortsLockEnt> {
   }
 
   loadLockEntry(raw: Json) {
-    const entry = lockValidator.parse(raw);
-
-    if (entry.version != "0") {
-      throw new Error(`unexepected version tag deserializing lockEntry`);
-    }
+    const entry = lockValidator.parse(
+      migrateLockEntry(raw, lockEntryVersion, lockEntryMigrations),
+    );
     const memoStore = getResolutionMemo(this.gcx);
     for (const [hash, config] of Object.entries(entry.configResolutions)) {
       logger().debug(
@@ -154,8 +167,33 @@ export class PortsModule extends ModuleBase<PortsLockEnt> {
       ),
     );
     return {
-      version: "0",
+      version: lockEntryVersion,
       configResolutions: JSON.parse(JSON.stringify(configResolutions)),
     };
   }
 }
+
+/**
+ * Declarative ghjkfiles can't import port modules so their install
+ * configs refer to the ports under ghjk's `ports` dir by module name
+ * instead. This resolves such configs into the fat configs the ports
+ * modules produce.
+ */
+async function reifyPortModule(config: unknown) {
+  if (
+    typeof config != "object" || config == null ||
+    !("portModule" in config) || typeof config.portModule != "string"
+  ) {
+    return config;
+  }
+  const { portModule, ...rest } = config;
+  if (!/^[a-z0-9_][a-z0-9_.-]*$/.test(portModule)) {
+    throw new Error(`invalid port module name "${portModule}"`);
+  }
+  const mod = await import(
+    new URL(`../../../ports/${portModule}.ts`, import.meta.url).href
+  ).catch((cause) => {
+    throw new Error(`unable to load port module "${portModule}"`, { cause });
+  });
+  return mod.default(rest);
+}
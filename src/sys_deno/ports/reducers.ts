@@ -71,13 +71,35 @@ async function reduceInstArts(
 
   // use this to track seen env vars to report conflicts
   const foundEnvVars: Record<string, [string, string]> = {};
-  // FIXME: detect shim conflicts
   // FIXME: better support for multi installs
-  await Promise.all(installGraph.user.map(async (instId) => {
-    const { binPaths, libPaths, includePaths, installPath, env } = installArts
-      .get(
-        instId,
-      )!;
+
+  // the globs are expanded concurrently but the provisions
+  // are emitted in the order of the install graph in order
+  // for shim conflicts to resolve deterministically
+  const expanded = await Promise.all(installGraph.user.map(async (instId) => {
+    const {
+      binPaths,
+      libPaths,
+      includePaths,
+      pkgConfigPaths,
+      manPaths,
+      sharePaths,
+      installPath,
+    } = installArts.get(instId)!;
+    const expandCurry = (path: string) =>
+      expandGlobsAndAbsolutize(path, installPath);
+    return await Promise.all([
+      Promise.all(binPaths.map(expandCurry)),
+      Promise.all(libPaths.map(expandCurry)),
+      Promise.all(includePaths.map(expandCurry)),
+      Promise.all((pkgConfigPaths ?? []).map(expandCurry)),
+      Promise.all((manPaths ?? []).map(expandCurry)),
+      Promise.all((sharePaths ?? []).map(expandCurry)),
+    ]);
+  }));
+
+  for (const [idx, instId] of installGraph.user.entries()) {
+    const { env } = installArts.get(instId)!;
     out.push({ ty: installProvisionTy, instId });
 
     for (const [key, val] of Object.entries(env)) {
@@ -102,38 +124,59 @@ async function reduceInstArts(
         val,
       });
     }
-    const expandCurry = (path: string) =>
-      expandGlobsAndAbsolutize(path, installPath);
-
-    const [binPathsNorm, libPathsNorm, includePathsNorm] = await Promise
-      .all(
-        [
-          Promise.all(binPaths.map(expandCurry)),
-          Promise.all(libPaths.map(expandCurry)),
-          Promise.all(includePaths.map(expandCurry)),
-        ],
-      );
+    const [
+      binPathsNorm,
+      libPathsNorm,
+      includePathsNorm,
+      pkgConfigPathsNorm,
+      manPathsNorm,
+      sharePathsNorm,
+    ] = expanded[idx];
     out.push(
       ...binPathsNorm.flatMap((paths) =>
         paths.map((absolutePath) => ({
           ty: "posix.exec" as const,
           absolutePath,
+          instId,
         }))
       ),
       ...libPathsNorm.flatMap((paths) =>
         paths.map((absolutePath) => ({
           ty: "posix.sharedLib" as const,
           absolutePath,
+          instId,
         }))
       ),
       ...includePathsNorm.flatMap((paths) =>
         paths.map((absolutePath) => ({
           ty: "posix.headerFile" as const,
           absolutePath,
+          instId,
+        }))
+      ),
+      ...pkgConfigPathsNorm.flatMap((paths) =>
+        paths.map((absolutePath) => ({
+          ty: "posix.pkgConfig" as const,
+          absolutePath,
+          instId,
+        }))
+      ),
+      ...manPathsNorm.flatMap((paths) =>
+        paths.map((absolutePath) => ({
+          ty: "posix.manPage" as const,
+          absolutePath,
+          instId,
+        }))
+      ),
+      ...sharePathsNorm.flatMap((paths) =>
+        paths.map((absolutePath) => ({
+          ty: "posix.shareDir" as const,
+          absolutePath,
+          instId,
         }))
       ),
     );
-  }));
+  }
 
   return out;
 }
@@ -954,6 +954,21 @@ export async function doInstallStage(
       ...baseArgs,
     }),
   );
+  const pkgConfigPaths = validators.stringArray.parse(
+    await port.listPkgConfigPaths({
+      ...baseArgs,
+    }),
+  );
+  const manPaths = validators.stringArray.parse(
+    await port.listManPaths({
+      ...baseArgs,
+    }),
+  );
+  const sharePaths = validators.stringArray.parse(
+    await port.listSharePaths({
+      ...baseArgs,
+    }),
+  );
   const env = zod.record(zod.string()).parse(
     await port.execEnv({
       ...baseArgs,
@@ -964,6 +979,9 @@ export async function doInstallStage(
     binPaths,
     libPaths,
     includePaths,
+    pkgConfigPaths,
+    manPaths,
+    sharePaths,
     installPath,
     downloadPath,
     installVersion,
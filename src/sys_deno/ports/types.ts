@@ -147,6 +147,9 @@ const allowDepSetHashed = zod.record(zod.string(), zod.string());
 const installSetHashed = zod.object({
   installs: zod.array(zod.string()),
   allowedBuildDeps: zod.string(),
+  // also allow the standard ports as build deps, used by
+  // declarative ghjkfiles that can't refer to them directly
+  stdDeps: zod.boolean().nullish(),
 });
 
 const installSet = zod.object({
@@ -191,6 +194,10 @@ const installArtifacts = zod.object({
   binPaths: zod.string().array(),
   libPaths: zod.string().array(),
   includePaths: zod.string().array(),
+  // NOTE: optional since installs made by older versions won't have these
+  pkgConfigPaths: zod.string().array().optional(),
+  manPaths: zod.string().array().optional(),
+  sharePaths: zod.string().array().optional(),
   installPath: zod.string(),
   downloadPath: zod.string(),
 });
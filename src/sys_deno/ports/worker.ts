@@ -74,6 +74,21 @@ async function onMsg(msg: MessageEvent<WorkerReq>) {
       ty: req.ty,
       payload: await port.listIncludePaths(req.arg),
     };
+  } else if (req.ty === "listPkgConfigPaths") {
+    res = {
+      ty: req.ty,
+      payload: await port.listPkgConfigPaths(req.arg),
+    };
+  } else if (req.ty === "listManPaths") {
+    res = {
+      ty: req.ty,
+      payload: await port.listManPaths(req.arg),
+    };
+  } else if (req.ty === "listSharePaths") {
+    res = {
+      ty: req.ty,
+      payload: await port.listSharePaths(req.arg),
+    };
   } else if (req.ty === "download") {
     await port.download(req.arg),
       res = {
@@ -119,6 +134,15 @@ type WorkerReq =
   } | {
     ty: "listIncludePaths";
     arg: ListBinPathsArgs;
+  } | {
+    ty: "listPkgConfigPaths";
+    arg: ListBinPathsArgs;
+  } | {
+    ty: "listManPaths";
+    arg: ListBinPathsArgs;
+  } | {
+    ty: "listSharePaths";
+    arg: ListBinPathsArgs;
   });
 
 type WorkerResp = {
@@ -136,6 +160,15 @@ type WorkerResp = {
 } | {
   ty: "listIncludePaths";
   payload: string[];
+} | {
+  ty: "listPkgConfigPaths";
+  payload: string[];
+} | {
+  ty: "listManPaths";
+  payload: string[];
+} | {
+  ty: "listSharePaths";
+  payload: string[];
 } | {
   ty: "execEnv";
   payload: Record<string, string>;
@@ -276,6 +309,51 @@ export class DenoWorkerPort extends PortBase {
     throw new Error(`unexpected response from worker ${JSON.stringify(res)}`);
   }
 
+  override async listPkgConfigPaths(
+    args: ListBinPathsArgs,
+  ) {
+    const req: WorkerReq = {
+      ty: "listPkgConfigPaths",
+      arg: args,
+      moduleSpecifier: this.manifest.moduleSpecifier,
+    };
+    const res = await this.call(req);
+    if (res.ty == "listPkgConfigPaths") {
+      return res.payload;
+    }
+    throw new Error(`unexpected response from worker ${JSON.stringify(res)}`);
+  }
+
+  override async listManPaths(
+    args: ListBinPathsArgs,
+  ) {
+    const req: WorkerReq = {
+      ty: "listManPaths",
+      arg: args,
+      moduleSpecifier: this.manifest.moduleSpecifier,
+    };
+    const res = await this.call(req);
+    if (res.ty == "listManPaths") {
+      return res.payload;
+    }
+    throw new Error(`unexpected response from worker ${JSON.stringify(res)}`);
+  }
+
+  override async listSharePaths(
+    args: ListBinPathsArgs,
+  ) {
+    const req: WorkerReq = {
+      ty: "listSharePaths",
+      arg: args,
+      moduleSpecifier: this.manifest.moduleSpecifier,
+    };
+    const res = await this.call(req);
+    if (res.ty == "listSharePaths") {
+      return res.payload;
+    }
+    throw new Error(`unexpected response from worker ${JSON.stringify(res)}`);
+  }
+
   override async download(args: DownloadArgs) {
     const req: WorkerReq = {
       ty: "download",
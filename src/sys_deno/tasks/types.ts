@@ -8,6 +8,8 @@ const taskDefBase = zod.object({
   desc: zod.string().nullish(),
   workingDir: zod.string().nullish(),
   dependsOn: zod.string().array().nullish(),
+  /** run the onEnter and onExit hooks of the task env around the task */
+  envHooks: zod.boolean().nullish(),
 });
 
 const taskDefFullBase = taskDefBase.merge(zod.object({
@@ -54,6 +54,114 @@ type CustomE2eTestCase = Omit<E2eTestCase, "ePoints" | "fs"> & {
   stdin: string;
 };
 
+const posixHookOptsScript = `
+set -eux
+ghjk envs cook slow
+start=$SECONDS
+# the failing hook aborts activation
+. .ghjk/envs/slow/activate.sh && exit 101
+# the slow hook got killed
+[ $((SECONDS - start)) -lt 15 ] || exit 102
+# aborting deactivated the env
+[ -z "\${SLOW_VAR-}" ] || exit 103
+# processes spawned by the slow hook got killed too
+sleep 6
+[ ! -e slow_done ] || exit 104
+`;
+
+const fishHookOptsScript = `
+set fish_trace 1
+ghjk envs cook slow
+set start (date +%s)
+# the failing hook aborts activation
+. .ghjk/envs/slow/activate.fish; and exit 101
+# the slow hook got killed
+test (math (date +%s) - $start) -lt 15; or exit 102
+# aborting deactivated the env
+set --query SLOW_VAR; and exit 103
+# processes spawned by the slow hook got killed too
+sleep 6
+test -e slow_done; and exit 104
+true
+`;
+
+const taskBackgroundHookScript = `
+set -eux
+ghjk x bg_hooked
+[ "$(cat bg_task)" = "ran" ] || exit 101
+# the background hook outlives the task but not its timeout
+for _ in $(seq 1 10); do
+  [ -e bg_started ] && break
+  sleep 1
+done
+[ -e bg_started ] || exit 102
+sleep 12
+[ ! -e bg_done ] || exit 103
+`;
+
+// waits on the background re-cook to signal the shell
+const waitNextfilePosix = `
+for _ in $(seq 1 60); do
+  [ -f "$GHJK_NEXTFILE" ] && break
+  sleep 1
+done
+`;
+
+const posixAutoSyncScript = (
+  mode: "off" | "sync" | "sync_timeout" | "background",
+) => `
+set -eux
+[ "$FRUIT" = "apple" ] || exit 101
+[ "$(ghjk envs status)" = "fresh" ] || exit 102
+sed -i 's/apple/banana/' ghjk.ts
+[ "$(ghjk envs status)" = "stale" ] || exit 103
+ghjk_hook 2> hook.err
+cat hook.err
+${
+  mode == "off"
+    ? `
+grep 'Possible drift' hook.err || exit 104
+# nothing gets re-cooked
+[ "$(ghjk envs status)" = "stale" ] || exit 105
+[ ! -e "$GHJK_NEXTFILE" ] || exit 106
+[ "$(ghjk envs status --auto-sync)" = "stale" ] || exit 107
+`
+    : mode == "sync"
+    ? `
+grep 're-cooked$' hook.err || exit 104
+[ "$(ghjk envs status)" = "fresh" ] || exit 105
+[ -f "$GHJK_NEXTFILE" ] || exit 106
+`
+    : `
+grep 're-cooking in the background' hook.err || exit 104
+${waitNextfilePosix}
+[ -f "$GHJK_NEXTFILE" ] || exit 106
+`
+}${
+  mode == "off" ? "" : `
+# the shell reloads the re-cooked env
+[ "$FRUIT" = "apple" ] || exit 108
+precmd
+[ "$FRUIT" = "banana" ] || exit 109
+[ "$(ghjk envs status)" = "fresh" ] || exit 110
+`
+}`;
+
+const fishAutoSyncScript = `
+set fish_trace 1
+test "$FRUIT" = "apple"; or exit 101
+sed -i 's/apple/banana/' ghjk.ts
+test (ghjk envs status) = "stale"; or exit 103
+ghjk_hook 2> hook.err
+cat hook.err
+grep 're-cooked$' hook.err; or exit 104
+test (ghjk envs status) = "fresh"; or exit 105
+test -f "$GHJK_NEXTFILE"; or exit 106
+# the shell reloads the re-cooked env
+emit fish_preexec
+test "$FRUIT" = "banana"; or exit 109
+`;
+
 const cases: CustomE2eTestCase[] = [
   {
     name: "bash_interactive",
@@ -74,6 +182,52 @@ const cases: CustomE2eTestCase[] = [
     ePoint: `fish -il`,
     stdin: fishInteractiveScript,
   },
+  {
+    name: "bash_hook_opts",
+    ePoint: `bash --rcfile "$BASH_ENV" -si`,
+    stdin: posixHookOptsScript,
+  },
+  {
+    name: "fish_hook_opts",
+    ePoint: `fish -il`,
+    stdin: fishHookOptsScript,
+  },
+  {
+    name: "task_background_hook",
+    ePoint: `bash -s`,
+    stdin: taskBackgroundHookScript,
+  },
+  {
+    name: "bash_auto_sync_off",
+    ePoint: `bash -s`,
+    stdin: posixAutoSyncScript("off"),
+    envVars: { GHJK_AUTO_SYNC: "off" },
+  },
+  {
+    name: "bash_auto_sync",
+    ePoint: `bash -s`,
+    stdin: posixAutoSyncScript("sync"),
+    envVars: { GHJK_AUTO_SYNC: "sync" },
+  },
+  {
+    // the re-cook is left to finish in the background on timeout
+    name: "bash_auto_sync_timeout",
+    ePoint: `bash -s`,
+    stdin: posixAutoSyncScript("sync_timeout"),
+    envVars: { GHJK_AUTO_SYNC: "sync", GHJK_AUTO_SYNC_TIMEOUT: "0" },
+  },
+  {
+    name: "bash_auto_sync_background",
+    ePoint: `bash -s`,
+    stdin: posixAutoSyncScript("background"),
+    envVars: { GHJK_AUTO_SYNC: "background" },
+  },
+  {
+    name: "fish_auto_sync",
+    ePoint: `fish`,
+    stdin: fishAutoSyncScript,
+    envVars: { GHJK_AUTO_SYNC: "sync" },
+  },
 ];
 
 harness(cases.map((testCase) => ({
@@ -84,8 +238,27 @@ export { sophon } from "@ghjk/ts/hack.ts";
 import { task, env } from "@ghjk/ts/hack.ts";
 
 env("main")
+  .var("FRUIT", "apple")
   .onEnter(task($ => $\`/bin/sh -c 'echo remark > marker'\`))
   .onExit(task($ => $\`/bin/sh -c 'rm marker'\`))
+
+env("slow")
+  .var("SLOW_VAR", "1")
+  .onEnter({ task: task($ => $\`/bin/sh -c 'sleep 4 && touch slow_done'\`), timeout: 1 })
+  .onEnter({ task: task($ => $\`/bin/sh -c 'exit 1'\`), failureMode: "abort" })
+
+env("bg")
+  .onEnter({
+    task: task($ => $\`/bin/sh -c 'touch bg_started && sleep 10 && touch bg_done'\`),
+    background: true,
+    timeout: 5,
+  })
+
+task("bg_hooked", {
+  inherit: "bg",
+  envHooks: true,
+  fn: ($) => $\`/bin/sh -c 'echo ran > bg_task'\`,
+})
 `,
   },
   ePoints: [{ cmd: testCase.ePoint, stdin: testCase.stdin }],
@@ -22,6 +22,9 @@ type CustomE2eTestCase =
     | {
       ghjkTs: string;
     }
+    | {
+      files: Record<string, string>;
+    }
   );
 
 const envVarTestEnvs: EnvDefArgs[] = [
@@ -156,6 +159,160 @@ test (dummy) = "foo"; or exit 102;
 test (dummy) = "main"; or exit 102;
 `;
 
+// the dummy port ships a pkg-config file, a man page,
+// a cmake package config and a data dir
+const shimDirsSecureConfig: FileArgs = {
+  tasks: {
+    paths: {
+      inherit: "main",
+      fn: async ($) => {
+        for (
+          const key of [
+            "PKG_CONFIG_PATH",
+            "MANPATH",
+            "XDG_DATA_DIRS",
+            "CMAKE_PREFIX_PATH",
+          ]
+        ) {
+          await $`echo ${$.env[key] ?? ""}`;
+        }
+      },
+    },
+  },
+};
+
+const shimDirsTestsPosix = `
+set -eux
+[ -e "\${PKG_CONFIG_PATH%%:*}/dummy.pc" ] || exit 101
+[ -e "\${MANPATH%%:*}/man1/dummy.1" ] || exit 102
+[ "$(cat "\${XDG_DATA_DIRS%%:*}/dummy/greeting.txt")" = "main" ] || exit 103
+[ -e "\${CMAKE_PREFIX_PATH%%:*}/lib/cmake/Dummy/DummyConfig.cmake" ] || exit 104
+
+# the system defaults are kept when the vars were unset
+ghjk_deactivate
+unset MANPATH XDG_DATA_DIRS
+. .ghjk/envs/main/activate.sh
+case "$MANPATH" in *:) ;; *) exit 105 ;; esac
+case ":$XDG_DATA_DIRS:" in *:/usr/share:*) ;; *) exit 106 ;; esac
+
+# task envs get them too
+ghjk x paths > paths.txt
+pc=$(sed -n 1p paths.txt)
+[ -e "\${pc%%:*}/dummy.pc" ] || exit 107
+man=$(sed -n 2p paths.txt)
+[ -e "\${man%%:*}/man1/dummy.1" ] || exit 108
+share=$(sed -n 3p paths.txt)
+[ -e "\${share%%:*}/dummy/greeting.txt" ] || exit 109
+cmake=$(sed -n 4p paths.txt)
+[ -e "\${cmake%%:*}/lib/cmake/Dummy/DummyConfig.cmake" ] || exit 110
+`;
+
+const shimDirsTestsFish = `
+set fish_trace 1
+test -e $PKG_CONFIG_PATH[1]/dummy.pc; or exit 101
+test -e $MANPATH[1]/man1/dummy.1; or exit 102
+test (cat $XDG_DATA_DIRS[1]/dummy/greeting.txt) = "main"; or exit 103
+test -e $CMAKE_PREFIX_PATH[1]/lib/cmake/Dummy/DummyConfig.cmake; or exit 104
+
+# the system defaults are kept when the vars were unset
+ghjk_deactivate
+set --erase MANPATH
+set --erase XDG_DATA_DIRS
+. .ghjk/envs/main/activate.fish
+contains -- "" $MANPATH; or exit 105
+contains -- /usr/share $XDG_DATA_DIRS; or exit 106
+
+# task envs get them too
+set paths (ghjk x paths)
+test -e (string split : $paths[1])[1]/dummy.pc; or exit 107
+test -e (string split : $paths[2])[1]/man1/dummy.1; or exit 108
+test -e (string split : $paths[3])[1]/dummy/greeting.txt; or exit 109
+test -e (string split : $paths[4])[1]/lib/cmake/Dummy/DummyConfig.cmake; or exit 110
+`;
+
+const declarativeGhjkToml = `
+[envs.main]
+installs = [{ port = "dummy", output = "main" }]
+
+[envs.main.vars]
+SONG = "ditto"
+GREETING = { cmd = ["echo", "hello"] }
+MOTD = { file = "motd.txt" }
+
+[envs.sss]
+vars = { SING = "Seoul Sonyo Sound" }
+
+[envs.yuki]
+inherit = false
+vars = { HUMM = "Soul Lady" }
+`;
+
+const declarativeGhjkJson = `
+{
+  // same as the toml version
+  "envs": {
+    "main": {
+      "installs": [{ "port": "dummy", "output": "main" }],
+      "vars": {
+        "SONG": "ditto",
+        "GREETING": { "cmd": ["echo", "hello"] },
+        "MOTD": { "file": "motd.txt" }
+      }
+    },
+    "sss": { "vars": { "SING": "Seoul Sonyo Sound" } },
+    "yuki": { "inherit": false, "vars": { "HUMM": "Soul Lady" } }
+  }
+}
+`;
+
+const declarativeTestsPosix = `
+set -ex
+# by default, we should be in main
+[ "$SONG" = "ditto" ] || exit 101
+[ "$GREETING" = "hello" ] || exit 102
+[ "$MOTD" = "be kind" ] || exit 103
+[ "$(dummy)" = "main" ] || exit 104
+[ "$GHJK_ENV" = "main" ] || exit 105
+
+# envs inherit from main by default
+ghjk envs cook sss
+. .ghjk/envs/sss/activate.sh
+[ "$SONG" = "ditto" ] || exit 106
+[ "$SING" = "Seoul Sonyo Sound" ] || exit 107
+[ "$(dummy)" = "main" ] || exit 108
+[ "$GHJK_ENV" = "sss" ] || exit 109
+
+ghjk envs cook yuki
+. .ghjk/envs/yuki/activate.sh
+[ "$SONG" = "ditto" ] && exit 110
+[ "$HUMM" = "Soul Lady" ] || exit 111
+[ "$GHJK_ENV" = "yuki" ] || exit 112
+`;
+
+const declarativeTestsFish = `
+set fish_trace 1
+# by default, we should be in main
+test "$SONG" = "ditto"; or exit 101
+test "$GREETING" = "hello"; or exit 102
+test "$MOTD" = "be kind"; or exit 103
+test (dummy) = "main"; or exit 104
+test "$GHJK_ENV" = "main"; or exit 105
+
+# envs inherit from main by default
+ghjk envs cook sss
+. .ghjk/envs/sss/activate.fish
+test "$SONG" = "ditto"; or exit 106
+test "$SING" = "Seoul Sonyo Sound"; or exit 107
+test (dummy) = "main"; or exit 108
+test "$GHJK_ENV" = "sss"; or exit 109
+
+ghjk envs cook yuki
+. .ghjk/envs/yuki/activate.fish
+test "$SONG" = "ditto"; and exit 110
+test "$HUMM" = "Soul Lady"; or exit 111
+test "$GHJK_ENV" = "yuki"; or exit 112
+`;
+
 const taskAliasTestBody = {
   posix: `
 set -ex
@@ -340,6 +497,31 @@ test "$E2" = "2"; or exit 102
 test "$E3" = "3"; or exit 103
 test (dummy) = "e1"; or exit 104
 `, // TODO: test inheritance of more props
+  },
+  {
+    name: "inheritance_tree",
+    ePoint: "fish",
+    envs: [
+      { name: "e1", vars: { E1: "1" }, installs: [dummy({ output: "e1" })] },
+      {
+        name: "e2",
+        inherit: "e1",
+        vars: { E2: "2" },
+      },
+      {
+        name: "e3",
+        inherit: "e2",
+        vars: { E3: "3" },
+      },
+    ],
+    stdin: `
+set fish_trace 1
+ghjk envs show e3 | grep '"sources"'; or exit 101
+ghjk envs show --tree e3 | grep -A1 '^e3 (' | grep 'posix.envVar E3'; or exit 102
+ghjk envs show --tree e3 | grep -A1 'e2 (' | grep 'posix.envVar E2'; or exit 103
+ghjk envs show --tree e3 | grep -A2 'e1 (' | grep 'posix.envVar E1'; or exit 104
+ghjk envs show --tree e3 | grep -A3 'e1 (' | grep 'ghjk.ports.InstallSetRef'; or exit 105
+`,
   },
   {
     name: "inheritance_diamond",
@@ -372,6 +554,278 @@ test "$E3" = "3"; or exit 103
 test "$E4" = "4"; or exit 104
 test (dummy) = "e1"; or exit 105
 `, // TODO: test inheritance of more props
+  },
+  {
+    name: "env_var_merge",
+    ePoint: "fish",
+    envs: [
+      { name: "e1", vars: { MY_PATH: "/e1", OVER: "e1" } },
+      {
+        name: "e2",
+        inherit: "e1",
+        vars: {
+          MY_PATH: { val: "/e2", merge: { strategy: "prepend" } },
+          OVER: "e2",
+        },
+      },
+      {
+        name: "e3",
+        inherit: "e2",
+        vars: {
+          MY_PATH: { val: "/e3", merge: { strategy: "append", separator: ";" } },
+        },
+      },
+      {
+        name: "e4",
+        inherit: "e1",
+        vars: { OVER: { val: "e4", merge: { strategy: "error" } } },
+      },
+      {
+        // the error policy of the parent applies to children
+        // that set the var without a policy of their own
+        name: "e5",
+        inherit: "e6",
+        vars: { STRICT: "e5" },
+      },
+      {
+        name: "e6",
+        vars: { STRICT: { val: "e6", merge: { strategy: "error" } } },
+      },
+    ],
+    stdin: `
+set fish_trace 1
+ghjk envs cook e3
+. .ghjk/envs/e3/activate.fish
+test "$MY_PATH" = "/e2:/e1;/e3"; or exit 101
+test "$OVER" = "e2"; or exit 102
+ghjk envs show --explain e3 | grep '"/e1"'; or exit 103
+ghjk envs cook e4; and exit 104
+ghjk envs cook e6; or exit 105
+ghjk envs cook e5; and exit 106
+true
+`,
+  },
+  {
+    name: "env_var_templates",
+    ePoint: "fish",
+    envs: [
+      {
+        name: "main",
+        vars: {
+          ROOT: "${ghjk.envDir}/root",
+          BIN: "${ROOT}/bin",
+          WHO: "${env.name}",
+          RAW: "$${NOT_EXPANDED}",
+          UNKNOWN: "${HOME}/bin",
+        },
+      },
+      { name: "cyclic", vars: { ONE: "${TWO}", TWO: "${ONE}" } },
+    ],
+    stdin: `
+set fish_trace 1
+test "$ROOT" = (realpath .ghjk/envs/main)/root; or exit 101
+test "$BIN" = "$ROOT/bin"; or exit 102
+test "$WHO" = "main"; or exit 103
+test "$RAW" = '\${NOT_EXPANDED}'; or exit 104
+ghjk envs cook cyclic; and exit 105
+# unknown references are kept verbatim
+test "$UNKNOWN" = '\${HOME}/bin'; or exit 106
+true
+`,
+  },
+  {
+    name: "env_var_templates_quoted",
+    ePoint: "bash -s",
+    envs: [
+      {
+        name: "main",
+        vars: {
+          QUOTED: "${ghjk.envDir}/it's $(touch pwned)",
+        },
+      },
+    ],
+    stdin: `
+set -ex
+[ "$QUOTED" = "$(realpath .ghjk/envs/main)/it's \$(touch pwned)" ] || exit 101
+ghjk_deactivate
+[ -z "\${QUOTED+x}" ] || exit 102
+[ ! -e pwned ] || exit 103
+`,
+  },
+  {
+    name: "envs_which",
+    ePoint: `bash -s`,
+    envs: installTestEnvs,
+    stdin: `
+set -eux
+ghjk envs which dummy foo && exit 101
+ghjk envs cook foo
+ghjk envs which dummy foo | grep 'dummy' || exit 102
+ghjk envs which nonexistent foo && exit 103
+true
+`,
+  },
+  {
+    name: "shim_dirs_bash",
+    ePoint: `bash -s`,
+    envs: installTestEnvs,
+    secureConfig: shimDirsSecureConfig,
+    stdin: shimDirsTestsPosix,
+  },
+  {
+    name: "shim_dirs_fish",
+    ePoint: `fish`,
+    envs: installTestEnvs,
+    secureConfig: shimDirsSecureConfig,
+    stdin: shimDirsTestsFish,
+  },
+  {
+    name: "envs_which_conflict",
+    ePoint: `bash -s`,
+    envs: [{
+      name: "main",
+      installs: [
+        dummy({ output: "first" }),
+        // leave out the env var to avoid a conflict on it
+        dummy({ output: "second", env: {} }),
+      ],
+    }],
+    stdin: `
+set -eux
+# the earlier install wins
+[ "$(dummy)" = "first" ] || exit 101
+ghjk envs cook main 2>&1 | grep 'shim conflict' || exit 102
+ghjk envs which dummy main > which.txt
+cat which.txt
+[ "$(wc -l < which.txt)" -eq 2 ] || exit 103
+provider="$(head -n 1 which.txt | sed 's/ (from .*)$//')"
+[ "$("$provider")" = "first" ] || exit 104
+shadowed="$(sed -n 2p which.txt | sed 's/^  shadows //; s/ (from .*)$//')"
+[ "$("$shadowed")" = "second" ] || exit 105
+`,
+  },
+  {
+    name: "envs_gc",
+    ePoint: `bash -s`,
+    envs: installTestEnvs,
+    stdin: `
+set -eux
+ghjk envs cook main
+mkdir -p .ghjk/envs/bogus
+ln -s .ghjk/envs/nowhere .ghjk/envs/dangling
+ghjk envs gc --dry-run | grep bogus || exit 101
+[ -d .ghjk/envs/bogus ] || exit 102
+ghjk envs gc
+[ ! -e .ghjk/envs/bogus ] || exit 103
+[ ! -L .ghjk/envs/dangling ] || exit 104
+[ -e .ghjk/envs/main/activate.sh ] || exit 105
+# task env dirs of other ghjkdirs are left alone
+foreign="\${TMPDIR:-/tmp}/ws_ghjkTaskEnv_foreign_$$"
+mkdir -p "$foreign"
+touch -d '2 days ago' "$foreign"
+ghjk envs gc
+[ -d "$foreign" ] || exit 106
+rm -rf "$foreign"
+`,
+  },
+  {
+    name: "envs_cook_all",
+    ePoint: `bash -s`,
+    envs: installTestEnvs,
+    stdin: `
+set -eux
+ghjk envs cook --all
+[ "$(. .ghjk/envs/main/activate.sh && dummy)" = "main" ] || exit 101
+[ "$(. .ghjk/envs/foo/activate.sh && dummy)" = "foo" ] || exit 102
+[ "$(ghjk envs status main)" = "fresh" ] || exit 103
+[ "$(ghjk envs status foo)" = "fresh" ] || exit 104
+`,
+  },
+  {
+    name: "envs_ls_json",
+    ePoint: `bash -s`,
+    envs: [...installTestEnvs, { name: "_hidden" }],
+    stdin: `
+set -eux
+ghjk envs cook main
+[ "$(ghjk envs ls | grep -c _hidden)" = "0" ] || exit 101
+ghjk envs ls --all | grep _hidden || exit 102
+ghjk envs ls --json | grep '"name": "_hidden"' && exit 103
+ghjk envs ls --all --json | grep '"name": "_hidden"' || exit 104
+[ "$(ghjk envs ls --json | grep -c '"isCooked": true')" = "1" ] || exit 105
+ghjk envs ls --json | grep '"ghjk.ports.InstallSetRef": 1' || exit 106
+`,
+  },
+  {
+    name: "builtin_dyn_vars",
+    ePoint: `bash -s`,
+    envs: [
+      {
+        name: "main",
+        vars: { GREETING: { cmd: ["echo", "hello"] } },
+      },
+      {
+        name: "versioned",
+        vars: { APP_VERSION: { file: "VERSION" } },
+      },
+    ],
+    stdin: `
+set -eux
+[ "$GREETING" = "hello" ] || exit 101
+printf '1.2.3\\n' > VERSION
+ghjk envs cook versioned
+. .ghjk/envs/versioned/activate.sh
+[ "$APP_VERSION" = "1.2.3" ] || exit 102
+`,
+  },
+  {
+    name: "secret_scrubbing",
+    ePoint: `bash -s`,
+    envVars: {
+      DUMMY_TOKEN: "hunter2hunter2",
+    },
+    envs: [
+      {
+        name: "main",
+        installs: [dummy({ output: "hunter2hunter2" })],
+      },
+    ],
+    stdin: `
+set -eux
+[ "$(dummy)" = "hunter2hunter2" ] || exit 101
+grep -q 'hunter2hunter2' .ghjk/lock.json && exit 102
+grep -q 'secret:DUMMY_TOKEN' .ghjk/lock.json || exit 103
+# scrubbed entries don't count as lockfile drift
+ghjk --locked envs cook main || exit 104
+ghjk lock check || exit 105
+# the patterns only affect the lockfile and don't invalidate the serialization
+[ "$(GHJK_SECRET_ENV_VARS='MY_*' ghjk print staleness)" = "fresh" ] || exit 106
+`,
+  },
+  {
+    name: "completion_candidates",
+    ePoint: `bash -s`,
+    envs: envVarTestEnvs,
+    stdin: `
+set -eux
+GHJK_COMPLETIONS=activators ghjk envs cook main
+grep -q 'yuki' .ghjk/envs/main/completions.bash || exit 101
+grep -q 'sss' .ghjk/envs/main/completions.fish || exit 102
+`,
+  },
+  {
+    name: "wrapper_shims",
+    ePoint: `bash -s`,
+    envs: installTestEnvs,
+    stdin: `
+set -eux
+GHJK_SHIMS=wrapper ghjk envs cook foo
+[ ! -L .ghjk/envs/foo/shims/bin/dummy ] || exit 101
+grep -q 'export GHJK_ENV=' .ghjk/envs/foo/shims/bin/dummy || exit 102
+[ "$(env -i .ghjk/envs/foo/shims/bin/dummy)" = "foo" ] || exit 103
+ghjk envs cook foo
+[ -L .ghjk/envs/foo/shims/bin/dummy ] || exit 104
+`,
   },
   {
     name: "task_aliases_bash",
@@ -439,11 +893,29 @@ test (dummy) = "e1"; or exit 105
     },
     stdin: taskAliasTestBody.fish,
   },
+  {
+    name: "declarative_toml_bash",
+    ePoint: `bash -s`,
+    files: { "ghjk.toml": declarativeGhjkToml, "motd.txt": "be kind\n" },
+    stdin: declarativeTestsPosix,
+  },
+  {
+    name: "declarative_toml_fish",
+    ePoint: `fish`,
+    files: { "ghjk.toml": declarativeGhjkToml, "motd.txt": "be kind\n" },
+    stdin: declarativeTestsFish,
+  },
+  {
+    name: "declarative_json_bash",
+    ePoint: `bash -s`,
+    files: { "ghjk.json": declarativeGhjkJson, "motd.txt": "be kind\n" },
+    stdin: declarativeTestsPosix,
+  },
 ];
 
 harness(cases.map((testCase) => ({
   ...testCase,
-  fs: {
+  fs: "files" in testCase ? testCase.files : {
     "ghjk.ts": "ghjkTs" in testCase ? testCase.ghjkTs : genTsGhjkFile(
       {
         secureConf: {
@@ -33,6 +33,16 @@ __ghjk_get_mtime_ts .ghjk/hash.json > tstamp
 echo '// hey' >> extra.ts
 ghjk sync
 test (cat tstamp) -lt (__ghjk_get_mtime_ts .ghjk/hash.json); or exit 101
+`,
+  },
+  {
+    name: "not_invalidated_touched",
+    stdin: `
+__ghjk_get_mtime_ts .ghjk/hash.json > tstamp
+sleep 1
+touch ghjk.ts extra.ts
+ghjk sync
+test (cat tstamp) = (__ghjk_get_mtime_ts .ghjk/hash.json); or exit 101
 `,
   },
   {
@@ -74,6 +84,181 @@ __ghjk_get_mtime_ts .ghjk/hash.json > tstamp
 echo '// hey' >> .ghjk/deno.jsonc
 ghjk sync
 test (cat tstamp) = (__ghjk_get_mtime_ts .ghjk/hash.json); or exit 102
+`,
+  },
+  {
+    name: "re_serialize_flag",
+    stdin: `
+__ghjk_get_mtime_ts .ghjk/hash.json > tstamp
+ghjk --re-serialize sync
+test (cat tstamp) -lt (__ghjk_get_mtime_ts .ghjk/hash.json); or exit 101
+`,
+  },
+  {
+    name: "locked_flag",
+    stdin: `
+ghjk --locked envs cook; or exit 101
+echo '// hey' >> ghjk.ts
+ghjk --locked envs cook; and exit 102
+GHJK_LOCKED=1 ghjk envs cook; and exit 103
+ghjk envs cook; or exit 104
+ghjk --locked envs cook; or exit 105
+`,
+  },
+  {
+    name: "flag_env_vars",
+    stdin: `
+ghjk sync
+GHJK_LOCKED=1 ghjk envs cook; or exit 101
+GHJK_LOCKED=true ghjk envs cook; or exit 102
+echo '// hey' >> ghjk.ts
+GHJK_LOCKED=1 ghjk envs cook; and exit 103
+GHJK_LOCKED=0 ghjk envs cook; or exit 104
+GHJK_RE_RESOLVE=1 RUST_LOG=debug ghjk sync 2>&1 | grep 're_resolve flag set'; or exit 105
+GHJK_RE_RESOLVE=0 RUST_LOG=debug ghjk sync 2>&1 | grep 're_resolve flag set'; and exit 106
+# re-resolving arrives at the same lockfile
+ghjk lock check; or exit 107
+__ghjk_get_mtime_ts .ghjk/hash.json > tstamp
+GHJK_RE_SERIALIZE=1 ghjk sync
+test (cat tstamp) -lt (__ghjk_get_mtime_ts .ghjk/hash.json); or exit 108
+`,
+  },
+  {
+    name: "lock_migrate",
+    stdin: `
+ghjk lock migrate | grep 'lockfile already up to date'; or exit 101
+# versions from the future are rejected
+ghjk deno eval 'const lock = JSON.parse(await Deno.readTextFile(".ghjk/lock.json")); lock.version = "999"; await Deno.writeTextFile(".ghjk/lock.json", JSON.stringify(lock));'
+ghjk lock migrate 2>&1 | grep 'unsupported lockfile version "999"'; or exit 102
+ghjk envs cook; and exit 103
+true
+`,
+  },
+  {
+    name: "lock_migrate_v0",
+    stdin: `
+# lockfiles of version 0 held the serialized config
+ghjk deno eval 'const lock = JSON.parse(await Deno.readTextFile(".ghjk/lock.json")); lock.version = "0"; lock.config = { modules: [], blackboard: {} }; await Deno.writeTextFile(".ghjk/lock.json", JSON.stringify(lock));'
+cp .ghjk/lock.json lock.v0.json
+ghjk lock migrate | grep 'lockfile migrated to version "1"'; or exit 101
+ghjk deno eval 'const lock = JSON.parse(await Deno.readTextFile(".ghjk/lock.json")); if (lock.version != "1" || "config" in lock) Deno.exit(1);'; or exit 102
+ghjk lock check; or exit 103
+# they're also upgraded in memory when loaded
+cp lock.v0.json .ghjk/lock.json
+ghjk sync; or exit 104
+ghjk envs cook; or exit 105
+`,
+  },
+  {
+    name: "lock_merge",
+    stdin: `
+echo '{"version":"1","sys_entries":{"ports":{"version":"0","configResolutions":{"aaa":{"portRef":"dummy@0.1.0","version":"1.0.0","buildDepConfigs":{}},"bbb":{"portRef":"dummy@0.1.0","version":"2.0.0","buildDepConfigs":{}}}}}}' > base.json
+echo '{"version":"1","sys_entries":{"ports":{"version":"0","configResolutions":{"aaa":{"portRef":"dummy@0.1.0","version":"1.1.0","buildDepConfigs":{}},"bbb":{"portRef":"dummy@0.1.0","version":"2.0.0","buildDepConfigs":{}},"ccc":{"portRef":"dummy@0.1.0","version":"3.0.0","buildDepConfigs":{}}}}}}' > ours.json
+echo '{"version":"1","sys_entries":{"ports":{"version":"0","configResolutions":{"aaa":{"portRef":"dummy@0.1.0","version":"1.2.0","buildDepConfigs":{}},"bbb":{"portRef":"dummy@0.1.0","version":"2.1.0","buildDepConfigs":{}}}}}}' > theirs.json
+ghjk lock merge base.json ours.json theirs.json; or exit 101
+# resolutions changed on both sides are dropped whole
+ghjk deno eval 'const { configResolutions: res } = JSON.parse(await Deno.readTextFile("ours.json")).sys_entries.ports; if ("aaa" in res || res.bbb.version != "2.1.0" || res.ccc.version != "3.0.0" || res.bbb.portRef != "dummy@0.1.0") Deno.exit(1);'; or exit 102
+# conflicting version tags fail the merge and leave ours untouched
+echo '{"version":"1","sys_entries":{"ports":{"version":"1","configResolutions":{}}}}' > ours.json
+echo '{"version":"1","sys_entries":{"ports":{"version":"2","configResolutions":{}}}}' > theirs.json
+cp ours.json ours.bak
+ghjk lock merge base.json ours.json theirs.json; and exit 103
+cmp ours.json ours.bak; or exit 104
+# the lockfile is not rewritten if nothing changed
+__ghjk_get_mtime_ts .ghjk/lock.json > tstamp
+ghjk sync
+test (cat tstamp) = (__ghjk_get_mtime_ts .ghjk/lock.json); or exit 105
+`,
+  },
+  {
+    name: "lock_check",
+    stdin: `
+ghjk sync
+ghjk lock check; or exit 101
+ghjk deno eval 'const lock = JSON.parse(await Deno.readTextFile(".ghjk/lock.json")); delete lock.sys_entries.envs; await Deno.writeTextFile(".ghjk/lock.json", JSON.stringify(lock));'
+ghjk lock check; and exit 102
+ghjk lock check --json | grep '"path": "/sys_entries/envs"'; or exit 103
+# the check doesn't write out the lockfile
+ghjk lock check; and exit 104
+ghjk sync
+ghjk lock check; or exit 105
+`,
+  },
+  {
+    name: "digest_memo",
+    stdin: `
+# files are only memoized once their mtime is old enough
+touch -d '2020-01-01 00:00:00' ghjk.ts extra.ts
+ghjk sync
+test -f .ghjk/digests.json; or exit 101
+grep extra.ts .ghjk/digests.json; or exit 102
+# a same-size edit that keeps the mtime and inode is served from the memo
+printf '%s' 'export const stuff = "howdy"' > extra.ts
+touch -d '2020-01-01 00:00:00' extra.ts
+test (ghjk print staleness) = "fresh"; or exit 103
+# entries that weren't looked up are dropped when the memo's written out
+ghjk deno eval 'const memo = JSON.parse(await Deno.readTextFile(".ghjk/digests.json")); memo.entries["/nowhere/gone.ts"] = Object.values(memo.entries)[0]; await Deno.writeTextFile(".ghjk/digests.json", JSON.stringify(memo));'
+touch -d '2019-01-01 00:00:00' ghjk.ts
+ghjk print staleness
+grep gone.ts .ghjk/digests.json; and exit 107
+# and is picked up once the memo's gone
+rm .ghjk/digests.json
+ghjk print staleness | grep 'read files changed: extra.ts'; or exit 104
+# the memo gets ignored in existing ghjkdirs
+printf 'envs\\nhash.json\\n' > .ghjk/.gitignore
+ghjk print staleness
+grep -x digests.json .ghjk/.gitignore; or exit 105
+grep -x hash.json .ghjk/.gitignore; or exit 106
+`,
+  },
+  {
+    name: "print_staleness",
+    stdin: `
+ghjk sync
+test (ghjk print staleness) = "fresh"; or exit 101
+MY_ENV=changed ghjk print staleness | grep 'env vars changed: MY_ENV'; or exit 102
+echo '// hey' >> extra.ts
+ghjk print staleness | grep 'read files changed: extra.ts'; or exit 103
+ghjk print staleness --json | grep '"reason": "readFiles"'; or exit 104
+rm .ghjk/hash.json
+test (ghjk print staleness) = "stale: no hashfile found"; or exit 105
+`,
+  },
+  {
+    name: "envs_status",
+    stdin: `
+ghjk sync main
+test (ghjk envs status main) = "fresh"; or exit 101
+test (ghjk envs status nonexistent) = "missing"; or exit 102
+echo '// hey' >> extra.ts
+test (ghjk envs status main) = "stale"; or exit 103
+ghjk sync main
+test (ghjk envs status main) = "fresh"; or exit 104
+test (MY_ENV=changed ghjk envs status main) = "stale"; or exit 105
+`,
+  },
+  {
+    name: "auto_sync_config",
+    stdin: `
+ghjk sync
+# the hooks' settings don't invalidate the serialization
+test (GHJK_AUTO_SYNC=sync ghjk print staleness) = "fresh"; or exit 101
+test (GHJK_AUTO_SYNC_TIMEOUT=3 ghjk print staleness) = "fresh"; or exit 102
+test (GHJK_AUTO_SYNC=sync ghjk envs status main) = "fresh"; or exit 103
+`,
+  },
+  {
+    name: "serialization_config",
+    stdin: `
+ghjk sync
+GHJK_SERIALIZATION_ALLOW_NET=example.com ghjk print staleness | grep 'ghjk config changed: serialization_permissions'; or exit 101
+# the ghjkfile's dir is always readable
+GHJK_SERIALIZATION_ALLOW_READ=/nonexistent GHJK_SERIALIZATION_ALLOW_NET= ghjk sync; or exit 102
+ghjk sync
+# the timeout doesn't invalidate the serialization
+test (GHJK_SERIALIZATION_TIMEOUT=60 ghjk print staleness) = "fresh"; or exit 103
+GHJK_SERIALIZATION_TIMEOUT=0 ghjk sync 2>&1 | grep 'serialization_timeout must be greater than zero'; or exit 104
+SLOW=1 GHJK_SERIALIZATION_TIMEOUT=1 ghjk sync 2>&1 | grep 'serialization timed out'; or exit 105
 `,
   },
 ];
@@ -87,6 +272,7 @@ import { task, env } from "@ghjk/ts/hack.ts";
 import {stuff} from "./extra.ts"
 
 await Array.fromAsync(Deno.readDir("dir"))
+if (Deno.env.get("SLOW")) await new Promise((res) => setTimeout(res, 5000))
 
 env("main")
   .vars({ hello: Deno.env.get("MY_ENV") ?? "world" })
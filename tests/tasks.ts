@@ -17,6 +17,12 @@ type CustomE2eTestCase =
     | {
       ghjkTs: string;
     }
+    | {
+      ghjkToml: string;
+    }
+    | {
+      files: Record<string, string>;
+    }
   );
 const cases: CustomE2eTestCase[] = [
   {
@@ -197,13 +203,120 @@ env("main")
 ghjk sync main
 cat output.txt
 test (cat output.txt) = 'A#STATIC, B#DYNAMIC'
+`,
+  },
+  {
+    name: "env_hooks",
+    ghjkTs: `
+export { sophon } from "@ghjk/ts/hack.ts";
+import { env, task } from "@ghjk/ts/hack.ts";
+
+env("main")
+  .onEnter(task({ fn: ($) => $\`echo enter >> hooks.log\` }))
+  .onExit(task({ fn: ($) => $\`echo exit >> hooks.log\` }))
+
+task("plain", ($) => $\`echo plain >> hooks.log\`)
+task("hooked", { envHooks: true, fn: ($) => $\`echo hooked >> hooks.log\` })
+`,
+    ePoint: `fish`,
+    stdin: `
+ghjk x plain
+test (cat hooks.log | string join ' ') = 'plain'; or exit 101
+rm hooks.log
+ghjk x hooked
+test (cat hooks.log | string join ' ') = 'enter hooked exit'; or exit 102
+`,
+  },
+  {
+    name: "declarative",
+    ghjkToml: `
+[envs.main.vars]
+GREETING = "hello"
+
+[tasks.greet]
+cmd = ["sh", "-c", 'echo "$GREETING $0 $NAME" >> greet.log']
+vars = { NAME = "world" }
+
+[tasks.greet-again]
+cmd = ["sh", "-c", 'echo "$GREETING $0" >> greet.log']
+dependsOn = ["greet"]
+`,
+    ePoint: `fish`,
+    stdin: `
+ghjk x greet-again there
+test (cat greet.log | string join ', ') = 'hello there world, hello there'; or exit 101
+`,
+  },
+  {
+    name: "workspace",
+    files: {
+      "ghjk.toml": `
+members = ["pkgs/*"]
+
+[tasks.test]
+cmd = ["sh", "-c", 'echo root >> $LOG']
+vars = { LOG = "test.log" }
+`,
+      "pkgs/pkga/ghjk.toml": `
+[envs.main.vars]
+NAME = "pkga"
+
+[envs.dev]
+vars = { NAME = "pkga-dev" }
+
+[tasks.prep]
+cmd = ["sh", "-c", 'echo "prep $NAME" >> ../../test.log']
+
+[tasks.test]
+cmd = ["sh", "-c", 'echo "test $NAME\${1:+ $1}" >> ../../test.log', "sh"]
+dependsOn = ["prep"]
+`,
+      "pkgs/pkgb/ghjk.toml": `
+[tasks.test]
+cmd = ["sh", "-c", 'echo "test pkgb" >> ../../test.log']
+`,
+      "pkgs/pkgc/ghjk.toml": `
+[envs.main.vars]
+PKG_DIR = { cmd = ["sh", "-c", "basename $PWD"] }
+
+[tasks.build]
+cmd = ["sh", "-c", 'echo "build $PKG_DIR" >> ../../test.log']
+`,
+    },
+    ePoint: `fish`,
+    stdin: `
+ghjk x pkga:test one
+test (cat test.log | string join ', ') = 'prep pkga, test pkga one'; or exit 101
+rm test.log
+ghjk x --all-members test
+test (cat test.log | string join ', ') = 'root, prep pkga, test pkga, test pkgb'; or exit 102
+ghjk x --all-members lint; and exit 103
+ghjk x test; or exit 104
+# new members matching the globs are picked up
+mkdir pkgs/pkgd
+printf '[tasks.test]\ncmd = ["sh", "-c", "echo test pkgd >> ../../test.log"]\n' > pkgs/pkgd/ghjk.toml
+ghjk print staleness | grep 'glob matches changed: pkgs/\\*'; or exit 105
+ghjk x pkgd:test; or exit 106
+test (tail -n 1 test.log) = 'test pkgd'; or exit 107
+# member envs are linked under the member's name
+ghjk envs cook pkga/dev; or exit 108
+ghjk envs gc
+. .ghjk/envs/pkga/dev/activate.fish; or exit 109
+test "$NAME" = "pkga-dev"; or exit 110
+# member cmd vars run in the member's dir
+ghjk x pkgc:build; or exit 111
+test (tail -n 1 test.log) = 'build pkgc'; or exit 112
 `,
   },
 ];
 
 harness(cases.map((testCase) => ({
   ...testCase,
-  fs: {
+  fs: "files" in testCase
+    ? testCase.files
+    : "ghjkToml" in testCase
+    ? { "ghjk.toml": testCase.ghjkToml }
+    : {
     "ghjk.ts": "ghjkTs" in testCase ? testCase.ghjkTs : genTsGhjkFile(
       {
         secureConf: {
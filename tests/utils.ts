@@ -148,7 +148,9 @@ export function genTsGhjkFile(
     // 2x
     (_, val) => typeof val == "string" ? val.replaceAll(/\\/g, "\\\\") : val,
     2,
-  );
+  )
+    // the json is embedded in a js template string
+    .replaceAll("${", "\\${");
 
   const tasks = Object.entries(secureConf?.tasks ?? {}).map(
     ([name, def]) => {
@@ -157,7 +159,7 @@ export function genTsGhjkFile(
         (_, val) =>
           typeof val == "string" ? val.replaceAll(/\\/g, "\\\\") : val,
         2,
-      );
+      ).replaceAll("${", "\\${");
       return $.dedent`
       ghjk.task({
         ...JSON.parse(\`${stringifiedSection}\`),